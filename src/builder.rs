@@ -0,0 +1,537 @@
+//! A builder for `Config`, so it can be made without naming every field.
+use std::time::Duration;
+
+use crate::{
+    merge_line_ranges, parse_globs, parse_timestamp, BinaryFiles, ColorChoice, Config,
+    MinigrepError, OutputFormat, SortOrder,
+};
+use crate::{DEFAULT_DELIMITER, DEFAULT_MATCH_COLOR, STDIN_FILENAME};
+
+/// Builds a `Config` one option at a time, leaving every option that
+/// isn't set at the same default as the command line does. Unlike a
+/// `Config` literal, code using it keeps compiling as options are added.
+///
+/// ## Example
+/// ```
+/// let config = minigrep::ConfigBuilder::new()
+///     .query("to")
+///     .filename("poem.txt")
+///     .case_sensitive(false)
+///     .show_line_numbers(true)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!("to", config.query);
+/// assert_eq!(vec!["poem.txt"], config.filenames);
+/// assert!(!config.regex);
+/// ```
+pub struct ConfigBuilder {
+    query: Option<String>,
+    config: Config,
+    /// Whether standard input is searched when no files are added.
+    stdin_by_default: bool,
+}
+
+/// Defines setters that store their argument in the config field of the
+/// same name.
+macro_rules! setters {
+    ($($(#[$doc:meta])* $name:ident: $ty:ty,)*) => {
+        $(
+            $(#[$doc])*
+            pub fn $name(mut self, $name: $ty) -> Self {
+                self.config.$name = $name;
+                self
+            }
+        )*
+    };
+}
+
+/// Defines setters for optional config fields, which are set to `Some`
+/// of their argument.
+macro_rules! optional_setters {
+    ($($(#[$doc:meta])* $name:ident: $ty:ty,)*) => {
+        $(
+            $(#[$doc])*
+            pub fn $name(mut self, $name: $ty) -> Self {
+                self.config.$name = Some($name);
+                self
+            }
+        )*
+    };
+}
+
+impl ConfigBuilder {
+    pub fn new() -> ConfigBuilder {
+        ConfigBuilder {
+            query: None,
+            config: Config {
+                query: String::new(),
+                filenames: Vec::new(),
+                case_sensitive: true,
+                show_line_numbers: false,
+                regex: false,
+                invert_match: false,
+                count_only: false,
+                before_context: 0,
+                after_context: 0,
+                word_match: false,
+                format: OutputFormat::Text,
+                max_count: None,
+                color: ColorChoice::Auto,
+                recursive: false,
+                only_matching: false,
+                fixed_string: false,
+                threads: None,
+                stream: false,
+                files_with_matches: false,
+                files_without_match: false,
+                binary_files: BinaryFiles::Skip,
+                quiet: false,
+                stats: false,
+                multiline: false,
+                byte_offset: false,
+                fuzzy: None,
+                max_line_length: None,
+                patterns: Vec::new(),
+                field: None,
+                delimiter: DEFAULT_DELIMITER.to_string(),
+                vimgrep: false,
+                check: false,
+                line_range: None,
+                mmap: false,
+                replace: None,
+                in_place: false,
+                verbose: false,
+                null: false,
+                null_data: false,
+                starts_with: false,
+                ends_with: false,
+                match_color: DEFAULT_MATCH_COLOR.to_string(),
+                group: false,
+                with_filename: None,
+                timeout: None,
+                decompress: false,
+                smart_case: false,
+                ignore_whitespace: false,
+                skip_blank: false,
+                shell_escape: false,
+                unique: false,
+                exclude_pattern: None,
+                debug_config: false,
+                progress: false,
+                paragraph: false,
+                max_depth: None,
+                include: Vec::new(),
+                exclude: Vec::new(),
+                hidden: false,
+                block: false,
+                lines: None,
+                span: false,
+                since: None,
+                until: None,
+                keep_untimed: false,
+                line_delimiter: None,
+                count_per_line: false,
+                all_terms: false,
+                follow: false,
+                absolute_path: false,
+                match_filename: false,
+                tabs: None,
+                heading_pattern: None,
+                sort: SortOrder::None,
+                retries: None,
+                strip_ansi: false,
+                print_original: false,
+                join: None,
+                encoding: None,
+                relative_to: None,
+                partial: false,
+            },
+            stdin_by_default: true,
+        }
+    }
+
+    /// The text, or regular expression in regex mode, to search for.
+    pub fn query<S: Into<String>>(mut self, query: S) -> Self {
+        self.query = Some(query.into());
+        self
+    }
+
+    /// Adds a file to search, after any added before it.
+    pub fn filename<S: Into<String>>(mut self, filename: S) -> Self {
+        self.config.filenames.push(filename.into());
+        self
+    }
+
+    /// Adds several files to search.
+    pub fn filenames<I: IntoIterator<Item = String>>(mut self, filenames: I) -> Self {
+        self.config.filenames.extend(filenames);
+        self
+    }
+
+    /// Adds the files read from a file list. The list is all there is to
+    /// search, so an empty one searches nothing rather than standard input.
+    pub(crate) fn listed_filenames(mut self, filenames: Vec<String>) -> Self {
+        self.stdin_by_default = false;
+        self.filenames(filenames)
+    }
+
+    /// Adds another pattern to search for alongside the query.
+    pub fn pattern<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.config.patterns.push(pattern.into());
+        self
+    }
+
+    /// What fields are split on with `field`.
+    pub fn delimiter<S: Into<String>>(mut self, delimiter: S) -> Self {
+        self.config.delimiter = delimiter.into();
+        self
+    }
+
+    /// The text that replaces every match.
+    pub fn replace<S: Into<String>>(mut self, replacement: S) -> Self {
+        self.config.replace = Some(replacement.into());
+        self
+    }
+
+    /// How many lines of context to print both before and after a match.
+    pub fn context(mut self, lines: usize) -> Self {
+        self.config.before_context = lines;
+        self.config.after_context = lines;
+        self
+    }
+
+    /// Leaves out the lines that match this, even when the query does.
+    pub fn exclude_pattern<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.config.exclude_pattern = Some(pattern.into());
+        self
+    }
+
+    /// Prints each match after the nearest line above it matching this
+    /// regular expression.
+    pub fn heading_pattern<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.config.heading_pattern = Some(pattern.into());
+        self
+    }
+
+    /// Prints every line joined into one, with this between them.
+    pub fn join<S: Into<String>>(mut self, separator: S) -> Self {
+        self.config.join = Some(separator.into());
+        self
+    }
+
+    /// Decodes files from the encoding with this label before searching.
+    pub fn encoding<S: Into<String>>(mut self, label: S) -> Self {
+        self.config.encoding = Some(label.into());
+        self
+    }
+
+    /// Prints file paths relative to this directory.
+    pub fn relative_to<S: Into<String>>(mut self, base: S) -> Self {
+        self.config.relative_to = Some(base.into());
+        self
+    }
+
+    /// Only searches the lines with a leading timestamp at or after this.
+    pub fn since<S: Into<String>>(mut self, timestamp: S) -> Self {
+        self.config.since = Some(timestamp.into());
+        self
+    }
+
+    /// Only searches the lines with a leading timestamp at or before this.
+    pub fn until<S: Into<String>>(mut self, timestamp: S) -> Self {
+        self.config.until = Some(timestamp.into());
+        self
+    }
+
+    /// Only searches the lines in these inclusive ranges of 1-based
+    /// numbers, in any order.
+    pub fn lines<I: IntoIterator<Item = (usize, usize)>>(mut self, lines: I) -> Self {
+        self.config.lines = Some(merge_line_ranges(lines.into_iter().collect()));
+        self
+    }
+
+    /// Adds a glob the files found by a recursive search may match to be
+    /// searched.
+    pub fn include<S: Into<String>>(mut self, glob: S) -> Self {
+        self.config.include.push(glob.into());
+        self
+    }
+
+    /// Adds a glob for the files and directories a recursive search skips.
+    pub fn exclude<S: Into<String>>(mut self, glob: S) -> Self {
+        self.config.exclude.push(glob.into());
+        self
+    }
+
+    /// Only searches the lines from `start` to `end`, counting from 1.
+    pub fn line_range(mut self, start: usize, end: usize) -> Self {
+        self.config.line_range = Some((start, end));
+        self
+    }
+
+    setters! {
+        /// Whether case matters. It does by default.
+        case_sensitive: bool,
+        /// Whether to print line numbers.
+        show_line_numbers: bool,
+        /// Whether the query is a regular expression.
+        regex: bool,
+        /// Whether to print the lines that don't match instead.
+        invert_match: bool,
+        /// Whether to print how many lines matched instead of the lines.
+        count_only: bool,
+        /// How many lines of context to print before a match.
+        before_context: usize,
+        /// How many lines of context to print after a match.
+        after_context: usize,
+        /// Whether the query only matches whole words.
+        word_match: bool,
+        /// How the results are printed.
+        format: OutputFormat,
+        /// When matches are highlighted.
+        color: ColorChoice,
+        /// Whether directories are searched through.
+        recursive: bool,
+        /// Whether to print only the matched parts of lines.
+        only_matching: bool,
+        /// Whether the query is taken literally, even in regex mode.
+        fixed_string: bool,
+        /// Whether files are read a line at a time, whatever their size.
+        stream: bool,
+        /// Whether to print only the names of files that match.
+        files_with_matches: bool,
+        /// Whether to print only the names of files that don't match.
+        files_without_match: bool,
+        /// What to do with files that aren't valid UTF-8.
+        binary_files: BinaryFiles,
+        /// The order each file's matching lines are printed in.
+        sort: SortOrder,
+        /// Whether ANSI escape sequences are removed before matching.
+        strip_ansi: bool,
+        /// Whether lines matched without their escape sequences are
+        /// printed with them.
+        print_original: bool,
+        /// Whether to print nothing and stop at the first match.
+        quiet: bool,
+        /// Whether to print a summary line at the end.
+        stats: bool,
+        /// Whether regular expressions match across lines.
+        multiline: bool,
+        /// Whether to print the byte offset of each line.
+        byte_offset: bool,
+        /// Whether to print `file:line:column:text` for every match.
+        vimgrep: bool,
+        /// Whether to only check that the search could run.
+        check: bool,
+        /// Whether to map files into memory instead of reading them.
+        mmap: bool,
+        /// Whether to rewrite files with their matches replaced.
+        in_place: bool,
+        /// Whether to report the lines scanned on stderr.
+        verbose: bool,
+        /// Whether file names end in a NUL byte.
+        null: bool,
+        /// Whether lines end in a NUL byte instead of a line feed.
+        null_data: bool,
+        /// Whether the query only matches at the start of a line.
+        starts_with: bool,
+        /// Whether the query only matches at the end of a line.
+        ends_with: bool,
+        /// The SGR codes highlighted matches are shown with, like `01;31`.
+        match_color: String,
+        /// Whether every file is gunzipped, whatever its name.
+        decompress: bool,
+        /// Whether case only matters for queries with an uppercase letter.
+        smart_case: bool,
+        /// Whether surrounding whitespace is ignored when matching.
+        ignore_whitespace: bool,
+        /// Whether blank lines are left out entirely.
+        skip_blank: bool,
+        /// Whether printed matches are quoted for a shell.
+        shell_escape: bool,
+        /// Whether each distinct matching line of a file is printed once.
+        unique: bool,
+        /// Whether to print the config instead of searching.
+        debug_config: bool,
+        /// Whether to show how far the search has got on stderr.
+        progress: bool,
+        /// Whether a recursive search goes into hidden files and directories.
+        hidden: bool,
+        /// Whether each match and its context is printed as a block.
+        block: bool,
+        /// Whether to print where the first and last matches are instead.
+        span: bool,
+        /// Whether whole paragraphs are matched and printed.
+        paragraph: bool,
+        /// Whether each file's lines are printed under a header with its name.
+        group: bool,
+        /// Whether lines without a timestamp are kept in a time window.
+        keep_untimed: bool,
+        /// Whether each matching line is printed with its occurrence count.
+        count_per_line: bool,
+        /// Whether a line has to contain every query and pattern to match.
+        all_terms: bool,
+        /// Whether a recursive search follows symlinks to directories.
+        follow: bool,
+        /// Whether files are named by their absolute paths in the output.
+        absolute_path: bool,
+        /// Whether a recursive search also reports files whose paths match.
+        match_filename: bool,
+        /// Whether the lines found before a timeout are still printed.
+        partial: bool,
+    }
+
+    optional_setters! {
+        /// Stops after this many matching lines in each file.
+        max_count: usize,
+        /// How many files to search at once.
+        threads: usize,
+        /// Matches lines with at most this many typos.
+        fuzzy: usize,
+        /// Cuts printed lines down to this many characters.
+        max_line_length: usize,
+        /// Only matches within this field, counting from 1.
+        field: usize,
+        /// How many directories deep a recursive search goes.
+        max_depth: usize,
+        /// Whether lines are prefixed with their file name, whatever the
+        /// number of files.
+        with_filename: bool,
+        /// Gives up on the search once it has taken this long.
+        timeout: Duration,
+        /// Expands tabs in printed lines to stops this many columns apart.
+        tabs: usize,
+        /// Reads a file this many more times after a transient failure.
+        retries: usize,
+        /// Ends lines with this ASCII character instead of a line feed.
+        line_delimiter: char,
+    }
+
+    /// Makes the config, searching standard input if no file was added.
+    ///
+    /// ## Errors
+    /// Returns `InvalidConfig` when there's no query, when a glob is
+    /// invalid, or when options conflict or need others that weren't set.
+    pub fn build(self) -> Result<Config, MinigrepError> {
+        let mut config = self.config;
+        config.query = match self.query {
+            Some(query) => query,
+            None => return Err(MinigrepError::invalid_config("Didn't get a query string")),
+        };
+
+        if config.multiline && !config.regex {
+            return Err(MinigrepError::invalid_config(
+                "--multiline only works with --regex",
+            ));
+        }
+
+        if config.filenames.is_empty() && self.stdin_by_default {
+            config.filenames.push(STDIN_FILENAME.to_string());
+        }
+
+        if config.in_place && config.replace.is_none() {
+            return Err(MinigrepError::invalid_config("--in-place needs --replace"));
+        }
+        if config.in_place && config.filenames.iter().any(|name| name == STDIN_FILENAME) {
+            return Err(MinigrepError::invalid_config(
+                "--in-place can't rewrite standard input",
+            ));
+        }
+
+        if config.field == Some(0) {
+            return Err(MinigrepError::invalid_config("Fields are numbered from 1"));
+        }
+        if config
+            .lines
+            .as_ref()
+            .is_some_and(|lines| lines.iter().any(|&(start, _)| start == 0))
+        {
+            return Err(MinigrepError::invalid_config("Lines are numbered from 1"));
+        }
+        if config
+            .line_delimiter
+            .is_some_and(|delimiter| !delimiter.is_ascii())
+        {
+            return Err(MinigrepError::invalid_config(
+                "The line delimiter must be an ASCII character",
+            ));
+        }
+        if let Some(label) = &config.encoding {
+            if encoding_rs::Encoding::for_label(label.as_bytes()).is_none() {
+                return Err(MinigrepError::InvalidConfig(format!(
+                    "Unknown encoding {}",
+                    label
+                )));
+            }
+        }
+        for timestamp in config.since.iter().chain(&config.until) {
+            if parse_timestamp(timestamp).is_none() {
+                return Err(MinigrepError::InvalidConfig(format!(
+                    "Invalid timestamp {}",
+                    timestamp
+                )));
+            }
+        }
+        match config.line_range {
+            Some((0, _)) => return Err(MinigrepError::invalid_config("Lines are numbered from 1")),
+            Some((start, end)) if start > end => {
+                return Err(MinigrepError::invalid_config(
+                    "The line range ends before it starts",
+                ))
+            }
+            _ => {}
+        }
+
+        parse_globs(&config.include)?;
+        parse_globs(&config.exclude)?;
+
+        config.check_conflicts()?;
+
+        Ok(config)
+    }
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        ConfigBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_match_the_command_line() {
+        let config = ConfigBuilder::new().query("rust").build().unwrap();
+
+        assert_eq!(vec![STDIN_FILENAME], config.filenames);
+        assert!(config.case_sensitive);
+        assert_eq!(ColorChoice::Auto, config.color);
+        assert_eq!(DEFAULT_DELIMITER, config.delimiter);
+    }
+
+    #[test]
+    fn build_checks_options() {
+        let error = |builder: ConfigBuilder| match builder.build() {
+            Err(MinigrepError::InvalidConfig(message)) => message,
+            _ => panic!("expected an invalid config"),
+        };
+
+        assert_eq!("Didn't get a query string", error(ConfigBuilder::new()));
+        assert_eq!(
+            "Lines are numbered from 1",
+            error(ConfigBuilder::new().query("rust").line_range(0, 2))
+        );
+        assert_eq!(
+            "-c/--count can't be used with -o/--only-matching",
+            error(
+                ConfigBuilder::new()
+                    .query("rust")
+                    .count_only(true)
+                    .only_matching(true)
+            )
+        );
+    }
+}
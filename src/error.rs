@@ -0,0 +1,82 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+/// Everything that can go wrong while configuring or running a search.
+#[derive(Debug)]
+pub enum MinigrepError {
+    /// The named file doesn't exist.
+    FileNotFound(String),
+    /// Any other failure to read or write.
+    Io(io::Error),
+    /// The command line arguments can't be turned into a `Config`.
+    InvalidConfig(String),
+    /// The query isn't a valid regular expression in regex mode.
+    Regex(regex::Error),
+    /// A glob pattern given as a file name matched no files.
+    UnmatchedPattern(String),
+    /// The search took longer than its timeout.
+    Timeout(Duration),
+}
+
+impl fmt::Display for MinigrepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MinigrepError::FileNotFound(filename) => write!(f, "{}: No such file", filename),
+            MinigrepError::Io(e) => write!(f, "{}", e),
+            MinigrepError::InvalidConfig(message) => write!(f, "{}", message),
+            MinigrepError::Regex(e) => write!(f, "Invalid regular expression: {}", e),
+            MinigrepError::UnmatchedPattern(pattern) => {
+                write!(f, "{}: No files match the pattern", pattern)
+            }
+            MinigrepError::Timeout(timeout) => {
+                write!(f, "The search timed out after {:?}", timeout)
+            }
+        }
+    }
+}
+
+impl Error for MinigrepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MinigrepError::Io(e) => Some(e),
+            MinigrepError::Regex(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MinigrepError {
+    fn from(e: io::Error) -> Self {
+        MinigrepError::Io(e)
+    }
+}
+
+impl From<regex::Error> for MinigrepError {
+    fn from(e: regex::Error) -> Self {
+        MinigrepError::Regex(e)
+    }
+}
+
+impl From<serde_json::Error> for MinigrepError {
+    fn from(e: serde_json::Error) -> Self {
+        MinigrepError::Io(e.into())
+    }
+}
+
+impl MinigrepError {
+    pub(crate) fn invalid_config(message: &str) -> Self {
+        MinigrepError::InvalidConfig(message.to_string())
+    }
+
+    /// Attributes a failure to open or read a file to that file, so a
+    /// missing file can be told apart from other IO errors.
+    pub(crate) fn for_file(filename: &str, e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::NotFound {
+            MinigrepError::FileNotFound(filename.to_string())
+        } else {
+            MinigrepError::Io(e)
+        }
+    }
+}
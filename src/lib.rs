@@ -1,141 +1,8997 @@
-//! # minigrep
-//!
-//! `minigrep` is a collection of utilities to make performing searches
-//! on an input file
-use std::env;
-use std::error::Error;
-use std::fs;
-
-pub struct Config {
-    pub query: String,
-    pub filename: String,
-    pub case_sensitive: bool,
-}
-
-impl Config {
-    pub fn new(mut args: env::Args) -> Result<Config, &'static str> {
-        args.next();
-
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a query string"),
-        };
-
-        let filename = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a file name"),
-        };
-
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
-
-        Ok(Config {
-            query,
-            filename,
-            case_sensitive,
-        })
-    }
-}
-
-/// Starts the search given a Config struct containing the pattern
-/// and the file name, and then prints out the results.
-///
-/// ## Panics
-/// It could panic if the file doesn't exist or unable to read.
-/// ```
-/// let config = minigrep::Config{
-///     query: "to".to_string(),
-///     filename: "file_that_does_not_exists.txt".to_string(),
-///     case_sensitive: false,
-/// };
-///
-/// assert!(minigrep::run(config).is_err());
-///
-/// ```
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.filename)?;
-
-    let results = if config.case_sensitive {
-        search(&config.query, &contents)
-    } else {
-        search_case_insensitive(&config.query, &contents)
-    };
-
-    for line in results {
-        println!("{}", line);
-    }
-
-    Ok(())
-}
-
-/// Searches for the query in contents with case sensitivity.
-///
-/// ## Example
-/// ```
-/// let query = "to";
-/// let contents = "\
-///     To here\n\
-///     but not there.\n\
-///     here to there.";
-///
-/// assert_eq!(vec!["here to there."], minigrep::search(query, &contents));
-/// ```
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    contents
-        .lines()
-        .filter(|line| line.contains(query))
-        .collect()
-}
-
-/// Searches for the query in contents with case insensitivity.
-///
-/// ## Example
-/// ```
-/// let query = "tO";
-/// let contents = "\
-///     To here\n\
-///     but not there.\n\
-///     here to there.";
-///
-/// assert_eq!(vec!["To here", "here to there."],
-///     minigrep::search_case_insensitive(query, &contents));
-/// ```
-pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
-
-    contents
-        .lines()
-        .filter(|line| line.to_lowercase().contains(&query))
-        .collect()
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn one_result() {
-        let query = "duct";
-        let contents = "\
-            Rust:\n\
-            safe, fast, productive.\n\
-            Pick three.\n\
-            Duct tape.";
-
-        assert_eq!(vec!["safe, fast, productive."], search(query, contents));
-    }
-
-    #[test]
-    fn case_insensitive() {
-        let query = "rUsT";
-        let contents = "\
-            Rust:\n\
-            safe, fast, productive.\n\
-            Pick three.\n\
-            Trust me.";
-
-        assert_eq!(
-            vec!["Rust:", "Trust me."],
-            search_case_insensitive(query, contents)
-        );
-    }
-}
+//! # minigrep
+//!
+//! `minigrep` is a collection of utilities to make performing searches
+//! on an input file
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+mod builder;
+mod error;
+mod rc;
+mod walk;
+
+pub use builder::ConfigBuilder;
+pub use error::MinigrepError;
+pub use rc::RC_FILENAME;
+
+use rc::Defaults;
+
+/// The file name that stands for standard input, which is also searched
+/// when no file name is given at all.
+pub const STDIN_FILENAME: &str = "-";
+
+/// How `run` prints its results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One line per match, optionally prefixed like grep.
+    Text,
+    /// A single JSON array with one object per matching line.
+    Json,
+}
+
+/// When `run` highlights the matched text with ANSI color codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Only when `run` prints to a terminal, and never for other writers.
+    Auto,
+    Always,
+    Never,
+}
+
+/// The order a file's matching lines are printed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    /// The order they're in in the file.
+    None,
+    /// The longest lines first.
+    Length,
+    /// The lines with the most occurrences of the query first.
+    Matches,
+}
+
+/// The SGR codes highlighted text is shown with, bold red, unless
+/// `MINIGREP_COLORS` says otherwise.
+const DEFAULT_MATCH_COLOR: &str = "01;31";
+
+/// The escape code that ends highlighted text.
+const HIGHLIGHT_END: &str = "\x1b[0m";
+
+/// What follows the path of a file reported because its name matches,
+/// telling it apart from a matching line.
+pub const FILENAME_MATCH_MARKER: &str = " (file name matches)";
+
+/// What fields are split on with `--field`, unless `--delimiter` says
+/// otherwise.
+pub const DEFAULT_DELIMITER: &str = ",";
+
+/// What a line cut short by `max_line_length` ends with.
+const TRUNCATION_MARKER: &str = "...";
+
+/// What to search for, where and how, as parsed from the command line
+/// by `Config::new` or put together with a `ConfigBuilder`. More fields
+/// may be added, so it can't be made with a struct literal outside this
+/// crate.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Config {
+    pub query: String,
+    pub filenames: Vec<String>,
+    pub case_sensitive: bool,
+    pub show_line_numbers: bool,
+    pub regex: bool,
+    pub invert_match: bool,
+    pub count_only: bool,
+    pub before_context: usize,
+    pub after_context: usize,
+    pub word_match: bool,
+    pub format: OutputFormat,
+    pub max_count: Option<usize>,
+    pub color: ColorChoice,
+    pub recursive: bool,
+    pub only_matching: bool,
+    pub fixed_string: bool,
+    pub threads: Option<usize>,
+    pub stream: bool,
+    pub files_with_matches: bool,
+    pub files_without_match: bool,
+    pub binary_files: BinaryFiles,
+    pub quiet: bool,
+    /// Whether a summary line like `3 matches in 2 files (5 files
+    /// searched)` follows the output. It's printed on stderr in JSON
+    /// format, so stdout stays a single JSON array. With `-l` or `-L` each
+    /// file counts one match at most, since its search stops at the first.
+    pub stats: bool,
+    pub multiline: bool,
+    pub byte_offset: bool,
+    pub fuzzy: Option<usize>,
+    pub max_line_length: Option<usize>,
+    pub patterns: Vec<String>,
+    pub field: Option<usize>,
+    pub delimiter: String,
+    pub vimgrep: bool,
+    pub check: bool,
+    pub line_range: Option<(usize, usize)>,
+    pub mmap: bool,
+    pub replace: Option<String>,
+    pub in_place: bool,
+    pub verbose: bool,
+    pub null: bool,
+    pub null_data: bool,
+    pub starts_with: bool,
+    /// Whether the match has to end the line. With `starts_with` as well,
+    /// the whole line has to be the query.
+    pub ends_with: bool,
+    pub match_color: String,
+    pub group: bool,
+    /// Whether lines are prefixed with their file name, which by default
+    /// they are when more than one file is searched.
+    pub with_filename: Option<bool>,
+    /// How long the search may take before it's abandoned.
+    pub timeout: Option<Duration>,
+    /// Whether every file is gunzipped before it's searched, and not just
+    /// the ones whose names end in `.gz`.
+    pub decompress: bool,
+    /// Whether case only matters when the query has an uppercase letter,
+    /// whatever `case_sensitive` says.
+    pub smart_case: bool,
+    /// Whether leading and trailing whitespace, of both the lines and the
+    /// queries, is ignored when matching.
+    pub ignore_whitespace: bool,
+    /// Whether lines with nothing but whitespace are left out entirely.
+    pub skip_blank: bool,
+    /// Whether the text of every printed match is quoted for a shell.
+    pub shell_escape: bool,
+    /// Whether each distinct matching line of a file is only picked once.
+    pub unique: bool,
+    /// Lines matching this, as a regular expression in regex mode, are
+    /// left out even when the query matches them.
+    pub exclude_pattern: Option<String>,
+    /// Whether to print the config instead of searching.
+    pub debug_config: bool,
+    /// Whether to show how far the search has got on stderr.
+    pub progress: bool,
+    /// Whether whole paragraphs, the runs of lines between blank lines,
+    /// are matched and printed instead of single lines.
+    pub paragraph: bool,
+    /// How many directories deep a recursive search goes below the ones
+    /// it's given, where 0 only searches the files directly inside them.
+    pub max_depth: Option<usize>,
+    /// When there are any, a recursive search only searches the files
+    /// matching one of these globs, by name or by path below the directory.
+    pub include: Vec<String>,
+    /// The files and directories a recursive search skips, as globs.
+    pub exclude: Vec<String>,
+    /// Whether a recursive search goes into the files and directories
+    /// whose names start with a `.`, which it skips otherwise.
+    pub hidden: bool,
+    /// Whether each match and its context is printed as an indented block
+    /// under a header with the file name and the block's first line number.
+    pub block: bool,
+    /// When set, only the lines in these sorted, separate and inclusive
+    /// ranges of 1-based numbers are searched.
+    pub lines: Option<Vec<(usize, usize)>>,
+    /// Whether to print where a file's first and last matches are, and how
+    /// many there are, instead of the lines.
+    pub span: bool,
+    /// Only searches the lines with a leading ISO-8601 timestamp at or
+    /// after this one, like `2024-05-01` or `2024-05-01T12:30:00`.
+    pub since: Option<String>,
+    /// Only searches the lines with a leading timestamp at or before this
+    /// one, which takes in all of its last day, minute or second when the
+    /// time is left out or cut short.
+    pub until: Option<String>,
+    /// Whether lines without a leading timestamp are still searched when
+    /// `since` or `until` is set.
+    pub keep_untimed: bool,
+    /// The ASCII character that ends each line of the input instead of a
+    /// line feed, like `;`. Printed lines end in it too, so the records
+    /// come out joined the way they went in.
+    pub line_delimiter: Option<char>,
+    /// Whether each matching line is printed after its line number and
+    /// how many times the query occurs in it, like `3:2:text`.
+    pub count_per_line: bool,
+    /// Whether a line only matches when it contains the query and every
+    /// pattern, rather than any of them. They're taken literally.
+    pub all_terms: bool,
+    /// Whether a recursive search follows symlinks to directories, which
+    /// are skipped otherwise. Each directory is still searched only once.
+    pub follow: bool,
+    /// Whether files are named by their canonical absolute paths in the
+    /// output, rather than the paths they were found by.
+    pub absolute_path: bool,
+    /// Whether a recursive search also reports the files whose paths
+    /// below the searched directory match the query, followed by
+    /// `FILENAME_MATCH_MARKER`.
+    pub match_filename: bool,
+    /// Expands tabs in printed lines to spaces, up to the next of the tab
+    /// stops this many columns apart. Matching and byte offsets still see
+    /// the tabs.
+    pub tabs: Option<usize>,
+    /// A regular expression for the lines that head sections, like
+    /// `^fn ` or `^#`. Each matching line is printed after the nearest
+    /// heading above it, which is printed once per section as
+    /// `number=heading`.
+    pub heading_pattern: Option<String>,
+    /// The order each file's matching lines are printed in. Lines that
+    /// sort the same keep their order. Files are never streamed when
+    /// their lines are sorted, since every match has to be found first.
+    pub sort: SortOrder,
+    /// How many more times a file is read after a transient failure, like
+    /// one from a log being rotated, waiting a little longer each time.
+    /// Files that don't exist or can't be read at all fail straight away.
+    pub retries: Option<usize>,
+    /// Whether ANSI escape sequences, like color codes, are removed from
+    /// lines before they're matched. The lines are printed without them
+    /// too, unless `print_original` is set.
+    pub strip_ansi: bool,
+    /// Whether lines matched with their escape sequences stripped are
+    /// printed as they were. Matches are still highlighted, or printed on
+    /// their own, where they are in the original line.
+    pub print_original: bool,
+    /// Joins every printed line into a single one, with this between
+    /// them, like `, `. The stats line stays on its own.
+    pub join: Option<String>,
+    /// The encoding files are in, like `latin1` or `utf-16le`, by any of
+    /// its WHATWG labels. They're decoded whole before they're searched,
+    /// with invalid sequences replaced by U+FFFD. A byte order mark wins
+    /// over it. Without it files are read as UTF-8.
+    pub encoding: Option<String>,
+    /// A directory the printed file paths are made relative to, rather
+    /// than left as they were found. Paths that can't be made relative to
+    /// it, like ones on another drive, are printed as absolute paths.
+    pub relative_to: Option<String>,
+    /// Whether the lines of the files searched before a search timed out
+    /// are still printed. Without it a search that times out prints
+    /// nothing, unless its lines were already printed live from stdin.
+    pub partial: bool,
+}
+
+impl Config {
+    /// Parses the command line arguments, falling back to the defaults in
+    /// the `RC_FILENAME` file of the current directory, if there is one,
+    /// for anything they leave out. Highlighted matches are shown in the
+    /// color given by the `ms` entry of the `MINIGREP_COLORS` environment
+    /// variable, like `ms=01;32` for bold green, or bold red without it.
+    ///
+    /// Options can appear before, between or after the query and file
+    /// names. Everything after a `--` argument is taken as a query or file
+    /// name, even if it starts with `-`. Every non-empty line of a file
+    /// given with `-f` is a pattern searched for instead of a query, so
+    /// every other argument is a file, like `grep -f`. So is the pattern
+    /// given with each `-p`, which searches it along with the ones from
+    /// any patterns files. With `--any a,b,c`
+    /// the comma-separated terms are searched for literally instead of a
+    /// query, and every other argument is a file.
+    /// `--all a,b,c` is the same, except that lines need every term.
+    ///
+    /// ## Errors
+    /// Returns `FileNotFound` or `Io` when a patterns file can't be read.
+    pub fn new<I: Iterator<Item = String>>(args: I) -> Result<Config, MinigrepError> {
+        Config::with_defaults(args, Defaults::load(Path::new(RC_FILENAME))?)
+    }
+
+    fn with_defaults<I: Iterator<Item = String>>(
+        mut args: I,
+        defaults: Defaults,
+    ) -> Result<Config, MinigrepError> {
+        args.next();
+
+        let mut builder = ConfigBuilder::new();
+        let mut before_context = None;
+        let mut after_context = None;
+        let mut color = None;
+        let mut case_flag = None;
+        let mut smart_case = false;
+        let mut alternatives = None;
+        let mut positional = Vec::new();
+
+        while let Some(arg) = args.next() {
+            // Long options may also be given their value as `--name=value`.
+            let (arg, mut inline) = match arg.find('=') {
+                Some(at) if arg.starts_with("--") => {
+                    (arg[..at].to_string(), Some(arg[at + 1..].to_string()))
+                }
+                _ => (arg, None),
+            };
+            let mut value = || inline.take().or_else(|| args.next());
+
+            builder = match arg.as_str() {
+                "-n" | "--line-number" => builder.show_line_numbers(true),
+                "-b" | "--byte-offset" => builder.byte_offset(true),
+                "-i" | "--ignore-case" => {
+                    case_flag = Some(false);
+                    builder
+                }
+                "-s" | "--case-sensitive" => {
+                    case_flag = Some(true);
+                    builder
+                }
+                "-e" | "--regex" => builder.regex(true),
+                "-F" | "--fixed-strings" => builder.fixed_string(true),
+                "-v" | "--invert-match" => builder.invert_match(true),
+                "-c" | "--count" => builder.count_only(true),
+                "-q" | "--quiet" => builder.quiet(true),
+                "--stats" => builder.stats(true),
+                "-l" | "--files-with-matches" => builder.files_with_matches(true),
+                "-L" | "--files-without-match" => builder.files_without_match(true),
+                "-w" | "--word-regexp" => builder.word_match(true),
+                "-r" | "--recursive" => builder.recursive(true),
+                "--threads" => builder.threads(parse_number(value())?),
+                "--sort" => builder.sort(match value().as_deref() {
+                    Some("none") => SortOrder::None,
+                    Some("length") => SortOrder::Length,
+                    Some("matches") => SortOrder::Matches,
+                    Some(_) => {
+                        return Err(MinigrepError::invalid_config(
+                            "Unknown sort order, expected length, matches or none",
+                        ))
+                    }
+                    None => return Err(MinigrepError::invalid_config("Didn't get a sort order")),
+                }),
+                "--binary-files" => builder.binary_files(match value().as_deref() {
+                    Some("skip") => BinaryFiles::Skip,
+                    Some("text") => BinaryFiles::Text,
+                    Some("lossy") => BinaryFiles::Lossy,
+                    Some(_) => {
+                        return Err(MinigrepError::invalid_config(
+                            "Unknown binary files choice, expected skip, text or lossy",
+                        ))
+                    }
+                    None => {
+                        return Err(MinigrepError::invalid_config(
+                            "Didn't get a binary files choice",
+                        ))
+                    }
+                }),
+                "--stream" => builder.stream(true),
+                "--mmap" => builder.mmap(true),
+                "--replace" => builder
+                    .replace(value().ok_or_else(|| {
+                        MinigrepError::invalid_config("Didn't get a replacement")
+                    })?),
+                "--in-place" => builder.in_place(true),
+                "--verbose" => builder.verbose(true),
+                "-Z" | "--null" => builder.null(true),
+                "-z" | "--null-data" => builder.null_data(true),
+                "--starts-with" => builder.starts_with(true),
+                "--ends-with" => builder.ends_with(true),
+                "--vimgrep" => builder.vimgrep(true),
+                "--group" => builder.group(true),
+                "--decompress" => builder.decompress(true),
+                "--smart-case" => {
+                    smart_case = true;
+                    builder.smart_case(true)
+                }
+                "--ignore-whitespace" => builder.ignore_whitespace(true),
+                "--skip-blank" => builder.skip_blank(true),
+                "--shell-escape" => builder.shell_escape(true),
+                "--unique" => builder.unique(true),
+                "--exclude-pattern" => match value() {
+                    Some(pattern) => builder.exclude_pattern(pattern),
+                    None => {
+                        return Err(MinigrepError::invalid_config(
+                            "Didn't get a pattern to exclude",
+                        ))
+                    }
+                },
+                "--since" => match value() {
+                    Some(timestamp) => builder.since(timestamp),
+                    None => {
+                        return Err(MinigrepError::invalid_config(
+                            "Didn't get a timestamp to search since",
+                        ))
+                    }
+                },
+                "--until" => match value() {
+                    Some(timestamp) => builder.until(timestamp),
+                    None => {
+                        return Err(MinigrepError::invalid_config(
+                            "Didn't get a timestamp to search until",
+                        ))
+                    }
+                },
+                "--keep-untimed" => builder.keep_untimed(true),
+                "--partial" => builder.partial(true),
+                "--follow" => builder.follow(true),
+                "--absolute-path" => builder.absolute_path(true),
+                "--relative-to" => builder.relative_to(value().ok_or_else(|| {
+                    MinigrepError::invalid_config("Didn't get a directory to be relative to")
+                })?),
+                "--match-filename" => builder.match_filename(true),
+                "--any" | "--all" => {
+                    let terms: Vec<String> = value()
+                        .unwrap_or_default()
+                        .split(',')
+                        .filter(|term| !term.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                    if terms.is_empty() {
+                        return Err(MinigrepError::invalid_config(
+                            "Didn't get any terms to match",
+                        ));
+                    }
+                    alternatives.get_or_insert_with(Vec::new).extend(terms);
+                    // The terms are taken literally, even in regex mode.
+                    builder.fixed_string(true).all_terms(arg == "--all")
+                }
+                "--only-count-per-line" => builder.count_per_line(true),
+                "--line-delimiter" => {
+                    let value = value().unwrap_or_default();
+                    let mut chars = value.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(delimiter), None) => builder.line_delimiter(delimiter),
+                        _ => {
+                            return Err(MinigrepError::invalid_config(
+                                "--line-delimiter takes a single character",
+                            ))
+                        }
+                    }
+                }
+                "-h" | "--no-filename" => builder.with_filename(false),
+                "-H" | "--with-filename" => builder.with_filename(true),
+                "--debug-config" => builder.debug_config(true),
+                "--progress" => builder.progress(true),
+                "--paragraph" => builder.paragraph(true),
+                "--block" => builder.block(true),
+                "--span" => builder.span(true),
+                "--check" => builder.check(true),
+                "--line-range" => {
+                    let (start, end) = parse_line_range(value())?;
+                    builder.line_range(start, end)
+                }
+                "--lines" => match value() {
+                    Some(spec) => builder.lines(parse_line_ranges(&spec)?),
+                    None => return Err(MinigrepError::invalid_config("Didn't get line numbers")),
+                },
+                "-U" | "--multiline" => builder.multiline(true),
+                "-o" | "--only-matching" => builder.only_matching(true),
+                "-m" | "--max-count" => builder.max_count(parse_number(value())?),
+                "--fuzzy" => builder.fuzzy(parse_number(value())?),
+                "--max-columns" => builder.max_line_length(parse_number(value())?),
+                "--tabs" => builder.tabs(parse_number(value())?),
+                "--retries" => builder.retries(parse_number(value())?),
+                "--join" => match value() {
+                    Some(separator) => builder.join(separator),
+                    None => return Err(MinigrepError::invalid_config("Didn't get a separator")),
+                },
+                "--encoding" => match value() {
+                    Some(encoding) => builder.encoding(encoding),
+                    None => return Err(MinigrepError::invalid_config("Didn't get an encoding")),
+                },
+                "--strip-ansi" => builder.strip_ansi(true),
+                "--print-original" => builder.print_original(true),
+                "--heading-pattern" => match value() {
+                    Some(pattern) => builder.heading_pattern(pattern),
+                    None => {
+                        return Err(MinigrepError::invalid_config(
+                            "Didn't get a heading pattern",
+                        ))
+                    }
+                },
+                "--field" => builder.field(parse_number(value())?),
+                "--hidden" => builder.hidden(true),
+                "--max-depth" => builder.max_depth(parse_number(value())?),
+                "--include" => builder.include(value().ok_or_else(|| {
+                    MinigrepError::invalid_config("Didn't get a glob to include")
+                })?),
+                "--exclude" => builder.exclude(value().ok_or_else(|| {
+                    MinigrepError::invalid_config("Didn't get a glob to exclude")
+                })?),
+                "--timeout" => builder.timeout(parse_seconds(value())?),
+                "--delimiter" => match value() {
+                    Some(value) if !value.is_empty() => builder.delimiter(value),
+                    _ => return Err(MinigrepError::invalid_config("Didn't get a delimiter")),
+                },
+                "-p" | "--pattern" => {
+                    let pattern = value()
+                        .filter(|pattern| !pattern.is_empty())
+                        .ok_or_else(|| MinigrepError::invalid_config("Didn't get a pattern"))?;
+                    alternatives.get_or_insert_with(Vec::new).push(pattern);
+                    builder
+                }
+                "-f" | "--file" => {
+                    let path = value().ok_or_else(|| {
+                        MinigrepError::invalid_config("Didn't get a patterns file")
+                    })?;
+                    let text =
+                        fs::read_to_string(&path).map_err(|e| MinigrepError::for_file(&path, e))?;
+                    alternatives.get_or_insert_with(Vec::new).extend(
+                        lines(&text)
+                            .filter(|pattern| !pattern.is_empty())
+                            .map(str::to_string),
+                    );
+                    builder
+                }
+                "--files0-from" => {
+                    let path = value()
+                        .ok_or_else(|| MinigrepError::invalid_config("Didn't get a file list"))?;
+                    builder.listed_filenames(read_file_list(&path)?)
+                }
+                "--color" => {
+                    color = match value().as_deref() {
+                        Some("auto") => Some(ColorChoice::Auto),
+                        Some("always") => Some(ColorChoice::Always),
+                        Some("never") => Some(ColorChoice::Never),
+                        Some(_) => {
+                            return Err(MinigrepError::invalid_config(
+                                "Unknown color choice, expected auto, always or never",
+                            ))
+                        }
+                        None => {
+                            return Err(MinigrepError::invalid_config("Didn't get a color choice"))
+                        }
+                    };
+                    builder
+                }
+                "--format" => builder.format(match value().as_deref() {
+                    Some("text") => OutputFormat::Text,
+                    Some("json") => OutputFormat::Json,
+                    Some(_) => {
+                        return Err(MinigrepError::invalid_config(
+                            "Unknown output format, expected text or json",
+                        ))
+                    }
+                    None => {
+                        return Err(MinigrepError::invalid_config("Didn't get an output format"))
+                    }
+                }),
+                "-B" | "--before-context" => {
+                    before_context = Some(parse_number(value())?);
+                    builder
+                }
+                "-A" | "--after-context" => {
+                    after_context = Some(parse_number(value())?);
+                    builder
+                }
+                "-C" | "--context" => {
+                    before_context = Some(parse_number(value())?);
+                    after_context = before_context;
+                    builder
+                }
+                "--" => {
+                    positional.extend(args.by_ref());
+                    break;
+                }
+                _ if arg.starts_with('-') && arg != STDIN_FILENAME => {
+                    return Err(MinigrepError::InvalidConfig(format!(
+                        "Unknown option {}",
+                        arg
+                    )))
+                }
+                _ => {
+                    positional.push(arg);
+                    continue;
+                }
+            };
+
+            if inline.is_some() {
+                return Err(MinigrepError::InvalidConfig(format!(
+                    "Option {} doesn't take a value",
+                    arg
+                )));
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        // With --any, --all, -p or -f the first term is the query and the rest
+        // are patterns, so every positional argument is a file.
+        // Smart case looks at every pattern, so they're kept until the case
+        // is decided.
+        let (query, patterns) = match alternatives {
+            Some(terms) => {
+                let mut terms = terms.into_iter();
+                (terms.next(), terms.collect())
+            }
+            None => (positional.next().or(defaults.query), Vec::new()),
+        };
+        let env_present = env::var("CASE_INSENSITIVE").is_ok();
+        // The defaults file counts only when neither the command line nor
+        // the environment variable says anything.
+        let cli_flag = match case_flag {
+            None if !env_present => defaults.case_sensitive,
+            flag => flag,
+        };
+        let queries = query.iter().chain(&patterns).map(String::as_str);
+        let case_sensitive = resolve_case_sensitivity(
+            cli_flag,
+            smart_case,
+            &queries.collect::<Vec<_>>().join("\n"),
+            env_present,
+        );
+        if let Some(query) = query {
+            builder = builder.query(query);
+        }
+        builder = patterns
+            .into_iter()
+            .fold(builder, |builder, term| builder.pattern(term));
+
+        builder
+            .filenames(positional)
+            .case_sensitive(case_sensitive)
+            .before_context(before_context.or(defaults.context).unwrap_or(0))
+            .after_context(after_context.or(defaults.context).unwrap_or(0))
+            .color(color.or(defaults.color).unwrap_or(ColorChoice::Auto))
+            .match_color(
+                env::var("MINIGREP_COLORS")
+                    .ok()
+                    .and_then(|spec| parse_match_color(&spec))
+                    .unwrap_or_else(|| DEFAULT_MATCH_COLOR.to_string()),
+            )
+            .build()
+    }
+
+    /// Checks that a search with this config could run, without running
+    /// it: the query is non-empty and valid, every file can be read, the
+    /// numeric options are in range and no options conflict.
+    ///
+    /// ## Errors
+    /// Returns `InvalidConfig` for a bad option, `Regex` for an invalid
+    /// regular expression, `UnmatchedPattern` for a glob pattern with no
+    /// files, and `FileNotFound` or `Io` for a file that can't be read.
+    ///
+    /// ## Example
+    /// ```
+    /// let args = ["minigrep", "name", "Cargo.toml"];
+    /// let config = minigrep::Config::new(args.iter().map(|arg| arg.to_string())).unwrap();
+    /// assert!(config.validate().is_ok());
+    ///
+    /// let args = ["minigrep", "name", "Cargo.tmol"];
+    /// let config = minigrep::Config::new(args.iter().map(|arg| arg.to_string())).unwrap();
+    /// assert!(matches!(
+    ///     config.validate(),
+    ///     Err(minigrep::MinigrepError::FileNotFound(_))
+    /// ));
+    /// ```
+    pub fn validate(&self) -> Result<(), MinigrepError> {
+        if self.query.is_empty() {
+            return Err(MinigrepError::invalid_config("The query is empty"));
+        }
+
+        for (value, option) in [
+            (self.max_count, "--max-count"),
+            (self.threads, "--threads"),
+            (self.max_line_length, "--max-columns"),
+        ] {
+            if value == Some(0) {
+                return Err(MinigrepError::InvalidConfig(format!(
+                    "{} must be at least 1",
+                    option
+                )));
+            }
+        }
+
+        self.check_conflicts()?;
+        Matcher::new(self)?;
+
+        if let Some(base) = &self.relative_to {
+            fs::read_dir(base).map_err(|e| MinigrepError::for_file(base, e))?;
+        }
+
+        for filename in &self.filenames {
+            if filename == STDIN_FILENAME {
+                continue;
+            }
+
+            for filename in expand_glob(filename)? {
+                let path = Path::new(&filename);
+                let readable = if path.is_dir() {
+                    if !self.recursive {
+                        return Err(MinigrepError::InvalidConfig(format!(
+                            "{}: Is a directory, use -r to search it",
+                            filename
+                        )));
+                    }
+                    fs::read_dir(path).map(drop)
+                } else {
+                    fs::File::open(path).map(drop)
+                };
+                readable.map_err(|e| MinigrepError::for_file(&filename, e))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rejects options that make no sense together:
+    ///
+    /// | option | conflicts with |
+    /// |--------|----------------|
+    /// | `-c` | `-o`, `-l`, `-L`, `--vimgrep` |
+    /// | `-l` | `-L`, `-n`, `-o`, `--vimgrep` |
+    /// | `-L` | `-n`, `-o`, `--vimgrep` |
+    /// | `-o` | `-v`, `--replace` |
+    /// | `--in-place` | `--binary-files=text/lossy`, `--encoding` |
+    /// | `--group` | `-l`, `-L`, `--vimgrep` |
+    /// | `--paragraph` | `-o`, `--vimgrep`, `--multiline`, `--in-place` |
+    /// | `--multiline` | `-F` |
+    /// | `--block` | `-o`, `--vimgrep`, `--paragraph` |
+    /// | `--span` | `-c`, `-l`, `-L`, `-o`, `--paragraph`, `--format json` |
+    /// | `--line-delimiter` | `-z` |
+    /// | `--only-count-per-line` | `-c`, `-o`, `-v`, `--vimgrep` |
+    /// | `--all` | `--fuzzy` |
+    /// | `--sort` | `-A`, `-B`, `-C`, `--block`, `--paragraph` |
+    /// | `--relative-to` | `--absolute-path` |
+    /// | `--format json` | `-c`, `-l`, `-L` |
+    fn check_conflicts(&self) -> Result<(), MinigrepError> {
+        let conflicts = [
+            (
+                self.count_only,
+                "-c/--count",
+                self.only_matching,
+                "-o/--only-matching",
+            ),
+            (
+                self.count_only,
+                "-c/--count",
+                self.files_with_matches,
+                "-l/--files-with-matches",
+            ),
+            (
+                self.count_only,
+                "-c/--count",
+                self.files_without_match,
+                "-L/--files-without-match",
+            ),
+            (self.count_only, "-c/--count", self.vimgrep, "--vimgrep"),
+            (
+                self.files_with_matches,
+                "-l/--files-with-matches",
+                self.files_without_match,
+                "-L/--files-without-match",
+            ),
+            (
+                self.files_with_matches,
+                "-l/--files-with-matches",
+                self.show_line_numbers,
+                "-n/--line-number",
+            ),
+            (
+                self.files_with_matches,
+                "-l/--files-with-matches",
+                self.only_matching,
+                "-o/--only-matching",
+            ),
+            (
+                self.files_with_matches,
+                "-l/--files-with-matches",
+                self.vimgrep,
+                "--vimgrep",
+            ),
+            (
+                self.files_without_match,
+                "-L/--files-without-match",
+                self.show_line_numbers,
+                "-n/--line-number",
+            ),
+            (
+                self.files_without_match,
+                "-L/--files-without-match",
+                self.only_matching,
+                "-o/--only-matching",
+            ),
+            (
+                self.files_without_match,
+                "-L/--files-without-match",
+                self.vimgrep,
+                "--vimgrep",
+            ),
+            (
+                self.only_matching,
+                "-o/--only-matching",
+                self.invert_match,
+                "-v/--invert-match",
+            ),
+            (
+                self.only_matching,
+                "-o/--only-matching",
+                self.replace.is_some(),
+                "--replace",
+            ),
+            (
+                self.in_place,
+                "--in-place",
+                self.binary_files.is_lossy(),
+                "--binary-files=text/lossy",
+            ),
+            (
+                self.group,
+                "--group",
+                self.files_with_matches,
+                "-l/--files-with-matches",
+            ),
+            (
+                self.group,
+                "--group",
+                self.files_without_match,
+                "-L/--files-without-match",
+            ),
+            (self.group, "--group", self.vimgrep, "--vimgrep"),
+            (
+                self.paragraph,
+                "--paragraph",
+                self.only_matching,
+                "-o/--only-matching",
+            ),
+            (self.paragraph, "--paragraph", self.vimgrep, "--vimgrep"),
+            (self.paragraph, "--paragraph", self.multiline, "--multiline"),
+            (
+                self.multiline,
+                "-U/--multiline",
+                self.fixed_string,
+                "-F/--fixed-strings",
+            ),
+            (self.paragraph, "--paragraph", self.in_place, "--in-place"),
+            (
+                self.block,
+                "--block",
+                self.only_matching,
+                "-o/--only-matching",
+            ),
+            (self.block, "--block", self.vimgrep, "--vimgrep"),
+            (self.block, "--block", self.paragraph, "--paragraph"),
+            (self.span, "--span", self.count_only, "-c/--count"),
+            (
+                self.span,
+                "--span",
+                self.files_with_matches,
+                "-l/--files-with-matches",
+            ),
+            (
+                self.span,
+                "--span",
+                self.files_without_match,
+                "-L/--files-without-match",
+            ),
+            (
+                self.span,
+                "--span",
+                self.only_matching,
+                "-o/--only-matching",
+            ),
+            (self.span, "--span", self.paragraph, "--paragraph"),
+            (
+                self.span,
+                "--span",
+                self.format == OutputFormat::Json,
+                "--format json",
+            ),
+            (
+                self.line_delimiter.is_some(),
+                "--line-delimiter",
+                self.null_data,
+                "-z/--null-data",
+            ),
+            (
+                self.count_per_line,
+                "--only-count-per-line",
+                self.count_only,
+                "-c/--count",
+            ),
+            (
+                self.count_per_line,
+                "--only-count-per-line",
+                self.only_matching,
+                "-o/--only-matching",
+            ),
+            (
+                self.count_per_line,
+                "--only-count-per-line",
+                self.invert_match,
+                "-v/--invert-match",
+            ),
+            (
+                self.count_per_line,
+                "--only-count-per-line",
+                self.vimgrep,
+                "--vimgrep",
+            ),
+            (self.all_terms, "--all", self.fuzzy.is_some(), "--fuzzy"),
+            (
+                self.relative_to.is_some(),
+                "--relative-to",
+                self.absolute_path,
+                "--absolute-path",
+            ),
+            (
+                self.in_place,
+                "--in-place",
+                self.encoding.is_some(),
+                "--encoding",
+            ),
+            (
+                self.sort != SortOrder::None,
+                "--sort",
+                self.before_context > 0 || self.after_context > 0,
+                "-A/-B/-C",
+            ),
+            (
+                self.sort != SortOrder::None,
+                "--sort",
+                self.block,
+                "--block",
+            ),
+            (
+                self.sort != SortOrder::None,
+                "--sort",
+                self.paragraph,
+                "--paragraph",
+            ),
+            (
+                self.format == OutputFormat::Json,
+                "--format json",
+                self.count_only,
+                "-c/--count",
+            ),
+            (
+                self.format == OutputFormat::Json,
+                "--format json",
+                self.files_with_matches,
+                "-l/--files-with-matches",
+            ),
+            (
+                self.format == OutputFormat::Json,
+                "--format json",
+                self.files_without_match,
+                "-L/--files-without-match",
+            ),
+        ];
+
+        match conflicts
+            .iter()
+            .find(|(first, _, second, _)| *first && *second)
+        {
+            Some((_, first, _, second)) => Err(MinigrepError::InvalidConfig(format!(
+                "{} can't be used with {}",
+                first, second
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the search minds case. In smart-case mode it does only when
+    /// the query or one of the patterns has an uppercase letter.
+    fn searches_case_sensitively(&self) -> bool {
+        let queries = self.queries().collect::<Vec<_>>().join("\n");
+        resolve_case_sensitivity(Some(self.case_sensitive), self.smart_case, &queries, false)
+    }
+
+    /// The query followed by the patterns read from patterns files, trimmed
+    /// when whitespace is ignored.
+    fn queries(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.query.as_str())
+            .chain(self.patterns.iter().map(String::as_str))
+            .map(move |query| {
+                if self.ignore_whitespace {
+                    query.trim()
+                } else {
+                    query
+                }
+            })
+    }
+}
+
+/// Shows every field on a line of its own, as `name: value` with the
+/// value as `Debug` shows it, in the order they're declared in. Strings
+/// are quoted and escaped, so the output is the same on every run and
+/// shows exactly what a value holds.
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        macro_rules! fields {
+            ($($name:ident,)*) => {
+                [$(format!("{}: {:?}", stringify!($name), self.$name)),*]
+            };
+        }
+
+        let fields = fields!(
+            query,
+            filenames,
+            case_sensitive,
+            show_line_numbers,
+            regex,
+            invert_match,
+            count_only,
+            before_context,
+            after_context,
+            word_match,
+            format,
+            max_count,
+            color,
+            recursive,
+            only_matching,
+            fixed_string,
+            threads,
+            stream,
+            files_with_matches,
+            files_without_match,
+            binary_files,
+            quiet,
+            stats,
+            multiline,
+            byte_offset,
+            fuzzy,
+            max_line_length,
+            patterns,
+            field,
+            delimiter,
+            vimgrep,
+            check,
+            line_range,
+            mmap,
+            replace,
+            in_place,
+            verbose,
+            null,
+            null_data,
+            starts_with,
+            ends_with,
+            match_color,
+            group,
+            with_filename,
+            timeout,
+            decompress,
+            smart_case,
+            ignore_whitespace,
+            skip_blank,
+            shell_escape,
+            unique,
+            exclude_pattern,
+            debug_config,
+            progress,
+            paragraph,
+            max_depth,
+            include,
+            exclude,
+            hidden,
+            block,
+            lines,
+            span,
+            since,
+            until,
+            keep_untimed,
+            line_delimiter,
+            count_per_line,
+            all_terms,
+            follow,
+            absolute_path,
+            match_filename,
+            tabs,
+            heading_pattern,
+            sort,
+            retries,
+            strip_ansi,
+            print_original,
+            join,
+            encoding,
+            relative_to,
+            partial,
+        );
+        write!(f, "{}", fields.join("\n"))
+    }
+}
+
+/// Decides whether a search minds case, from the highest precedence rule
+/// that applies:
+///
+/// 1. In smart-case mode it does only when `query` has an uppercase
+///    letter, whatever `-s` or `-i` said.
+/// 2. Otherwise `-s` or `-i` on the command line, given as `cli_flag`.
+/// 3. Otherwise it doesn't when the `CASE_INSENSITIVE` environment
+///    variable is set, given as `env_present`.
+/// 4. Otherwise it does.
+///
+/// ## Example
+/// ```
+/// use minigrep::resolve_case_sensitivity;
+///
+/// assert!(resolve_case_sensitivity(None, false, "rust", false));
+/// assert!(!resolve_case_sensitivity(None, false, "rust", true));
+/// assert!(resolve_case_sensitivity(Some(true), false, "rust", true));
+/// assert!(!resolve_case_sensitivity(Some(true), true, "rust", false));
+/// ```
+pub fn resolve_case_sensitivity(
+    cli_flag: Option<bool>,
+    smart_case: bool,
+    query: &str,
+    env_present: bool,
+) -> bool {
+    if smart_case {
+        query.chars().any(char::is_uppercase)
+    } else if let Some(case_sensitive) = cli_flag {
+        case_sensitive
+    } else {
+        !env_present
+    }
+}
+
+/// Reads the ISO-8601 timestamp at the start of the text, like
+/// `2024-05-01`, `2024-05-01T12:30` or `2024-05-01 12:30:05.250Z`, into
+/// its year, month, day, hour, minute, second and nanosecond, as far as
+/// they're given, followed by the rest of the text. Time zones aren't
+/// taken into account, so timestamps compare as they're written.
+fn leading_timestamp(text: &str) -> Option<(Vec<u32>, &str)> {
+    fn digits(text: &str, count: usize) -> Option<(u32, &str)> {
+        let part = text.get(..count)?;
+        if !part.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        Some((part.parse().ok()?, &text[count..]))
+    }
+    fn after<'t>(text: &'t str, separators: &[char]) -> Option<&'t str> {
+        text.strip_prefix(|c| separators.contains(&c))
+    }
+
+    let (year, rest) = digits(text, 4)?;
+    let (month, rest) = digits(after(rest, &['-'])?, 2)?;
+    let (day, mut rest) = digits(after(rest, &['-'])?, 2)?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let mut parts = vec![year, month, day];
+
+    let time = after(rest, &['T', ' ']).and_then(|time| {
+        let (hour, time) = digits(time, 2)?;
+        let (minute, time) = digits(after(time, &[':'])?, 2)?;
+        (hour < 24 && minute < 60).then_some((hour, minute, time))
+    });
+    if let Some((hour, minute, time)) = time {
+        parts.extend([hour, minute]);
+        rest = time;
+        if let Some((second, time)) = after(rest, &[':']).and_then(|time| digits(time, 2)) {
+            if second > 60 {
+                return None;
+            }
+            parts.push(second);
+            rest = time;
+            if let Some(fraction) = after(rest, &['.', ',']) {
+                let length = fraction.bytes().take_while(u8::is_ascii_digit).count();
+                if length > 0 {
+                    // Further digits are finer than a nanosecond.
+                    let nanos = format!("{:0<9}", &fraction[..length.min(9)]);
+                    parts.push(nanos.parse().ok()?);
+                    rest = &fraction[length..];
+                }
+            }
+        }
+    }
+    Some((parts, rest))
+}
+
+/// Reads a `--since` or `--until` timestamp, which is nothing but a
+/// timestamp, with an optional `Z` after it.
+pub(crate) fn parse_timestamp(text: &str) -> Option<Vec<u32>> {
+    match leading_timestamp(text)? {
+        (parts, "") | (parts, "Z") => Some(parts),
+        _ => None,
+    }
+}
+
+/// Whether the line's leading timestamp falls between `since` and
+/// `until`, where a line without one is in the window only when untimed
+/// lines are kept. `until` takes in everything it's a prefix of.
+fn in_time_window(
+    line: &str,
+    since: Option<&[u32]>,
+    until: Option<&[u32]>,
+    keep_untimed: bool,
+) -> bool {
+    match leading_timestamp(line) {
+        Some((parts, _)) => {
+            since.is_none_or(|since| parts.as_slice() >= since)
+                && until.is_none_or(|until| &parts[..parts.len().min(until.len())] <= until)
+        }
+        None => keep_untimed,
+    }
+}
+
+/// Searches for the query in contents with case sensitivity, only looking
+/// at the lines whose leading ISO-8601 timestamp is from `since` to
+/// `until`, inclusive. Either bound can be left out, and `until` takes in
+/// all of its last day, minute or second when it's cut short. Lines
+/// without a leading timestamp are searched only when `keep_untimed` is
+/// set.
+///
+/// ## Errors
+/// Returns `InvalidConfig` when `since` or `until` isn't a timestamp.
+///
+/// ## Example
+/// ```
+/// let contents = "\
+///     2024-05-01T09:00:00 disk full\n\
+///     2024-05-02T10:30:00 disk full\n\
+///     \tretrying the disk\n\
+///     2024-05-03T08:00:00 disk full";
+///
+/// assert_eq!(vec!["2024-05-02T10:30:00 disk full"],
+///     minigrep::search_time_range("disk", contents, Some("2024-05-02"), Some("2024-05-02"), false).unwrap());
+/// assert_eq!(vec!["2024-05-02T10:30:00 disk full", "\tretrying the disk"],
+///     minigrep::search_time_range("disk", contents, Some("2024-05-02"), Some("2024-05-02"), true).unwrap());
+/// ```
+pub fn search_time_range<'a>(
+    query: &str,
+    contents: &'a str,
+    since: Option<&str>,
+    until: Option<&str>,
+    keep_untimed: bool,
+) -> Result<Vec<&'a str>, MinigrepError> {
+    let since = since.map(time_bound).transpose()?;
+    let until = until.map(time_bound).transpose()?;
+    Ok(lines(contents)
+        .filter(|line| {
+            in_time_window(line, since.as_deref(), until.as_deref(), keep_untimed)
+                && line.contains(query)
+        })
+        .collect())
+}
+
+/// Reads a bound of a time window, failing for anything but a timestamp.
+fn time_bound(text: &str) -> Result<Vec<u32>, MinigrepError> {
+    parse_timestamp(text)
+        .ok_or_else(|| MinigrepError::InvalidConfig(format!("Invalid timestamp {}", text)))
+}
+
+/// The SGR codes for matched text in a `GREP_COLORS`-style spec like
+/// `ms=01;31:ln=32`, from its `ms` entry or else its `mt` entry. Other
+/// entries are ignored, and `None` means the spec is malformed or has
+/// neither entry.
+fn parse_match_color(spec: &str) -> Option<String> {
+    let mut color = None;
+    for entry in spec.split(':').filter(|entry| !entry.is_empty()) {
+        let (name, codes) = entry.split_once('=')?;
+        let valid = !codes.is_empty() && codes.chars().all(|c| c.is_ascii_digit() || c == ';');
+        match name {
+            "ms" | "mt" if !valid => return None,
+            "ms" => color = Some(codes),
+            "mt" => color = color.or(Some(codes)),
+            _ => {}
+        }
+    }
+
+    color.map(str::to_string)
+}
+
+/// Parses a `START:END` range of 1-based line numbers, where either end
+/// may be left out to mean the first or the last line.
+fn parse_line_range(arg: Option<String>) -> Result<(usize, usize), MinigrepError> {
+    let arg = arg.ok_or_else(|| MinigrepError::invalid_config("Didn't get a line range"))?;
+    let invalid = || MinigrepError::invalid_config("Expected a line range like START:END");
+
+    let (start, end) = arg.split_once(':').ok_or_else(invalid)?;
+    let start = match start {
+        "" => 1,
+        start => start.parse().map_err(|_| invalid())?,
+    };
+    let end = match end {
+        "" => usize::MAX,
+        end => end.parse().map_err(|_| invalid())?,
+    };
+
+    Ok((start, end))
+}
+
+/// The most line numbers `parse_line_spec` lists. `--lines` keeps the
+/// ranges instead, so it takes spans of any length.
+const MAX_LISTED_LINES: usize = 1 << 20;
+
+/// Parses a comma-separated list of 1-based line numbers and inclusive
+/// `START-END` ranges of them into the sorted line numbers, each once.
+///
+/// ## Errors
+/// Returns `InvalidConfig` when an entry isn't a number or a range, when
+/// a line number is 0, when a range ends before it starts, or when the
+/// list has more than 1,048,576 lines.
+///
+/// ## Example
+/// ```
+/// assert_eq!(
+///     vec![3, 7, 15, 16, 17],
+///     minigrep::parse_line_spec("15-17,3,7,16").unwrap()
+/// );
+/// assert!(minigrep::parse_line_spec("3,,7").is_err());
+/// ```
+pub fn parse_line_spec(s: &str) -> Result<Vec<usize>, MinigrepError> {
+    let ranges = parse_line_ranges(s)?;
+    let count = ranges
+        .iter()
+        .map(|&(start, end)| end - start + 1)
+        .fold(0, usize::saturating_add);
+    if count > MAX_LISTED_LINES {
+        return Err(MinigrepError::invalid_config(
+            "The line list is too long to list every line",
+        ));
+    }
+
+    Ok(ranges
+        .into_iter()
+        .flat_map(|(start, end)| start..=end)
+        .collect())
+}
+
+/// Parses a line list like `parse_line_spec`, into sorted inclusive
+/// ranges, with the ones that overlap or touch merged. A line number on
+/// its own is a range of one line.
+///
+/// ## Errors
+/// Fails like `parse_line_spec`, except that the list can be any length.
+fn parse_line_ranges(s: &str) -> Result<Vec<(usize, usize)>, MinigrepError> {
+    let invalid =
+        || MinigrepError::InvalidConfig(format!("Expected line numbers like 3,7,15-20, not {}", s));
+    let number = |text: &str| match text.trim().parse() {
+        Ok(0) => Err(MinigrepError::invalid_config("Lines are numbered from 1")),
+        Ok(number) => Ok(number),
+        Err(_) => Err(invalid()),
+    };
+
+    let mut lines = Vec::new();
+    for entry in s.split(',') {
+        match entry.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (number(start)?, number(end)?);
+                if start > end {
+                    return Err(MinigrepError::invalid_config(
+                        "The line range ends before it starts",
+                    ));
+                }
+                lines.push((start, end));
+            }
+            None => {
+                let line = number(entry)?;
+                lines.push((line, line));
+            }
+        }
+    }
+
+    Ok(merge_line_ranges(lines))
+}
+
+/// Sorts the inclusive line ranges and merges the ones that overlap or
+/// touch.
+pub(crate) fn merge_line_ranges(mut ranges: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn parse_number(arg: Option<String>) -> Result<usize, MinigrepError> {
+    match arg {
+        Some(arg) => arg
+            .parse()
+            .map_err(|_| MinigrepError::invalid_config("Expected a number after the option")),
+        None => Err(MinigrepError::invalid_config(
+            "Didn't get a number for the option",
+        )),
+    }
+}
+
+/// Parses a number of seconds, which may have a fraction, like `0.5`.
+fn parse_seconds(arg: Option<String>) -> Result<Duration, MinigrepError> {
+    let seconds = arg.ok_or_else(|| {
+        MinigrepError::invalid_config("Didn't get a number of seconds for the option")
+    })?;
+
+    seconds
+        .parse()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| {
+            MinigrepError::invalid_config("Expected a number of seconds after the option")
+        })
+}
+
+/// Decides whether a single line matches, according to the options in a
+/// `Config`, against the query and every pattern read with `-f`, any of
+/// which may match. Regular expressions are compiled once up front so they can
+/// be reused for every line of every file. Fuzzy mode takes precedence
+/// over fixed-string mode, which takes precedence over regex mode.
+enum Matcher {
+    Literal {
+        queries: Vec<String>,
+        case_sensitive: bool,
+        word: bool,
+        starts_with: bool,
+        ends_with: bool,
+        /// Whether every query has to match, rather than any.
+        all: bool,
+    },
+    Regex(Regex),
+    Fuzzy {
+        queries: Vec<String>,
+        case_sensitive: bool,
+        max_distance: usize,
+    },
+}
+
+impl Matcher {
+    fn new(config: &Config) -> Result<Matcher, regex::Error> {
+        let case_sensitive = config.searches_case_sensitively();
+        if let Some(max_distance) = config.fuzzy {
+            return Ok(Matcher::Fuzzy {
+                queries: config.queries().map(str::to_string).collect(),
+                case_sensitive,
+                max_distance,
+            });
+        }
+
+        if config.regex && !config.fixed_string && !config.all_terms {
+            let mut pattern = config
+                .queries()
+                .map(|query| format!("(?:{})", query))
+                .collect::<Vec<_>>()
+                .join("|");
+            if config.word_match {
+                pattern = format!(r"(?-u:\b)(?:{})(?-u:\b)", pattern);
+            }
+            if config.starts_with {
+                pattern = format!("^(?:{})", pattern);
+            }
+            if config.ends_with {
+                pattern = format!("(?:{})$", pattern);
+            }
+            if !case_sensitive {
+                pattern.insert_str(0, "(?i)");
+            }
+            if config.multiline {
+                pattern.insert_str(0, "(?m)");
+            }
+
+            return Ok(Matcher::Regex(Regex::new(&pattern)?));
+        }
+
+        Ok(Matcher::literal(config))
+    }
+
+    /// Matches the exclude pattern, as a regular expression in regex mode
+    /// and as plain text otherwise, minding only the case option.
+    fn exclusion(config: &Config, pattern: &str) -> Result<Matcher, regex::Error> {
+        let case_sensitive = config.searches_case_sensitively();
+        if config.regex && !config.fixed_string {
+            let flags = if case_sensitive { "" } else { "(?i)" };
+            return Ok(Matcher::Regex(Regex::new(&format!(
+                "{}{}",
+                flags, pattern
+            ))?));
+        }
+
+        Ok(Matcher::plain(pattern, case_sensitive))
+    }
+
+    /// Matches the text wherever it is in a line.
+    fn plain(text: &str, case_sensitive: bool) -> Matcher {
+        Matcher::Literal {
+            queries: vec![if case_sensitive {
+                text.to_string()
+            } else {
+                text.to_lowercase()
+            }],
+            case_sensitive,
+            word: false,
+            starts_with: false,
+            ends_with: false,
+            all: false,
+        }
+    }
+
+    /// Matches the queries as plain text, with the case and word options
+    /// of the config.
+    fn literal(config: &Config) -> Matcher {
+        let case_sensitive = config.searches_case_sensitively();
+        let queries = config
+            .queries()
+            .map(|query| {
+                if case_sensitive {
+                    query.to_string()
+                } else {
+                    query.to_lowercase()
+                }
+            })
+            .collect();
+
+        Matcher::Literal {
+            queries,
+            case_sensitive,
+            word: config.word_match,
+            starts_with: config.starts_with,
+            ends_with: config.ends_with,
+            all: config.all_terms,
+        }
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        match self {
+            Matcher::Literal {
+                queries,
+                case_sensitive,
+                word,
+                starts_with,
+                ends_with,
+                all,
+            } => {
+                let line: Cow<str> = if *case_sensitive {
+                    Cow::Borrowed(line)
+                } else {
+                    Cow::Owned(line.to_lowercase())
+                };
+
+                let matches = |query: &String| {
+                    let bounded = |range: Range<usize>| !*word || is_word_bounded(&line, &range);
+
+                    if *starts_with && *ends_with {
+                        line.as_ref() == query.as_str()
+                    } else if *starts_with || *ends_with {
+                        (!*starts_with
+                            || line.starts_with(query.as_str()) && bounded(0..query.len()))
+                            && (!*ends_with
+                                || line.ends_with(query.as_str())
+                                    && bounded(line.len() - query.len()..line.len()))
+                    } else if *word {
+                        contains_word(&line, query)
+                    } else {
+                        line.contains(query.as_str())
+                    }
+                };
+                if *all {
+                    queries.iter().all(matches)
+                } else {
+                    queries.iter().any(matches)
+                }
+            }
+            Matcher::Regex(re) => re.is_match(line),
+            Matcher::Fuzzy {
+                queries,
+                case_sensitive,
+                max_distance,
+            } => queries
+                .iter()
+                .any(|query| fuzzy_find(query, line, *case_sensitive).0 <= *max_distance),
+        }
+    }
+
+    /// Byte ranges of every non-empty match in the line.
+    fn find_ranges(&self, line: &str) -> Vec<Range<usize>> {
+        match self {
+            Matcher::Literal {
+                queries,
+                case_sensitive,
+                word,
+                starts_with,
+                ends_with,
+                ..
+            } => {
+                let mut ranges: Vec<Range<usize>> = queries
+                    .iter()
+                    .flat_map(|query| literal_ranges(line, query, *case_sensitive))
+                    .filter(|range| !*word || is_word_bounded(line, range))
+                    .filter(|range| !*starts_with || range.start == 0)
+                    .filter(|range| !*ends_with || range.end == line.len())
+                    .collect();
+
+                // Where the occurrences of different queries overlap, the
+                // one starting first wins, and then the longest.
+                ranges.sort_by_key(|range| (range.start, usize::MAX - range.end));
+                let mut end = 0;
+                ranges.retain(|range| {
+                    let kept = range.start >= end;
+                    if kept {
+                        end = range.end;
+                    }
+                    kept
+                });
+                ranges
+            }
+            Matcher::Regex(re) => re
+                .find_iter(line)
+                .map(|found| found.range())
+                .filter(|range| !range.is_empty())
+                .collect(),
+            Matcher::Fuzzy {
+                queries,
+                case_sensitive,
+                max_distance,
+            } => queries
+                .iter()
+                .map(|query| fuzzy_find(query, line, *case_sensitive))
+                .find(|(distance, range)| distance <= max_distance && !range.is_empty())
+                .map(|(_, range)| vec![range])
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Starts the search given a Config struct containing the pattern
+/// and the file names, and then prints out the results.
+///
+/// When more than one file is searched, every printed match is prefixed
+/// with the name of the file it came from. A file name of `-` reads from
+/// stdin instead. In recursive mode a directory is replaced by every file
+/// underneath it, skipping files that aren't valid UTF-8.
+///
+/// In multiline mode the regular expression is matched against the whole
+/// contents of each file, so `\n` in the pattern matches a line break,
+/// `^` and `$` match at the start and end of every line, and `(?s)` lets
+/// `.` match a line break too. A match that spans several lines prints
+/// every line it touches, each with its own line number, as if they had
+/// all matched, with the part of the match on each of them highlighted;
+/// `-o` prints the matched text itself, line breaks and all, under the
+/// number of the line it starts on.
+///
+/// In vimgrep mode every match is printed as `file:line:column:text`, as
+/// editors expect, with the file name even when only one file is searched
+/// and no context lines. A line with several matches is printed once for
+/// each of them. Columns are 1-based and count bytes, not characters, so
+/// `é` takes up two of them.
+///
+/// With `replace` set, every match on a printed line is replaced by the
+/// replacement, where `$1` or `${name}` stand for a capture group in
+/// regex mode. With `in_place` as well, nothing is printed and each file
+/// is rewritten with its matches replaced instead.
+///
+/// With `mmap` set, files are mapped into memory and searched in place
+/// instead of being copied into a buffer first. The files mustn't be
+/// truncated while they're searched.
+///
+/// Files are searched in parallel on `threads` worker threads, which
+/// defaults to the available parallelism, but the output is always in the
+/// order the files were given.
+///
+/// Returns whether any line matched, which the CLI turns into grep's exit
+/// codes: 0 for a match, 1 for no match and 2 for any error. In quiet mode
+/// nothing is printed and the search stops at the first match.
+///
+/// ## Errors
+/// A file that doesn't exist or is unable to be read is reported on
+/// stderr and skipped, so the remaining files are still searched. The
+/// first such error is returned afterwards, as `FileNotFound` for a
+/// missing file. A query that isn't a valid regular expression in regex
+/// mode fails straight away.
+///
+/// A search that takes longer than its `timeout` fails with `Timeout`. It
+/// prints nothing, or with `partial` set, the lines of the files searched
+/// before it ran out. The
+/// clock is looked at between files and every few lines while a file is
+/// scanned, rather than by another thread, so a single read that blocks,
+/// like one from an idle pipe, isn't cut short.
+/// ```
+/// let config = minigrep::ConfigBuilder::new()
+///     .query("to")
+///     .filename("file_that_does_not_exists.txt")
+///     .case_sensitive(false)
+///     .color(minigrep::ColorChoice::Never)
+///     .build()
+///     .unwrap();
+///
+/// assert!(matches!(
+///     minigrep::run(config),
+///     Err(minigrep::MinigrepError::FileNotFound(_))
+/// ));
+///
+/// ```
+pub fn run(mut config: Config) -> Result<bool, MinigrepError> {
+    // Stdout flushes after every line on its own, which makes printing
+    // many matches slow, so it's buffered and flushed once at the end.
+    let stdout = io::stdout();
+    if config.color == ColorChoice::Auto {
+        config.color = if stdout.is_terminal() {
+            ColorChoice::Always
+        } else {
+            ColorChoice::Never
+        };
+    }
+    run_to_writer(&config, &mut io::BufWriter::new(stdout.lock()))
+}
+
+/// Searches the files named in a Config struct like `run` does, but
+/// writes the results to `writer` instead of stdout, each ending in a
+/// single `\n` whatever the platform. They end in a NUL byte instead in
+/// null-data mode, and so do the file names listed by `-l` or `-L` when
+/// `null` is set.
+///
+/// `ColorChoice::Auto` never colors the output, since the writer isn't
+/// known to be a terminal. `run` decides it for stdout before calling
+/// this.
+///
+/// When standard input is the only input and its matching lines are
+/// printed as they are, they are written and flushed as soon as each one
+/// is read, so `tail -f log | minigrep error` shows them live.
+///
+/// ## Errors
+/// Fails in the same situations as `run`, and also with `Io` when the
+/// results can't be written. The lines gathered before a file failed to
+/// be searched are still written.
+///
+/// ## Example
+/// ```
+/// let args = ["minigrep", "-s", "name = ", "Cargo.toml"];
+/// let config = minigrep::Config::new(args.iter().map(|arg| arg.to_string())).unwrap();
+/// let mut output = Vec::new();
+///
+/// assert!(minigrep::run_to_writer(&config, &mut output).unwrap());
+/// assert_eq!(b"name = \"minigrep\"\n".to_vec(), output);
+/// ```
+pub fn run_to_writer<W: Write>(config: &Config, writer: &mut W) -> Result<bool, MinigrepError> {
+    if searches_stdin_live(config) {
+        return search_stdin_live(config, writer);
+    }
+
+    let mut results = Vec::new();
+    let outcome = search_files(config, &mut results);
+
+    if !config.quiet {
+        let terminator = if config.null && (config.files_with_matches || config.files_without_match)
+        {
+            '\0'
+        } else {
+            terminator(config) as char
+        };
+        for line in results {
+            write!(writer, "{}{}", line, terminator)?;
+        }
+        writer.flush()?;
+    }
+
+    outcome
+}
+
+/// Searches the files named in a Config struct like `run` does, but
+/// returns the formatted result lines instead of printing them. The lines
+/// already include any filename or line-number prefixes.
+///
+/// ## Errors
+/// Fails in the same situations as `run`. Files that can't be read are
+/// still reported on stderr as they are encountered.
+/// ```
+/// let config = minigrep::ConfigBuilder::new()
+///     .query("to")
+///     .filename("file_that_does_not_exists.txt")
+///     .case_sensitive(false)
+///     .color(minigrep::ColorChoice::Never)
+///     .build()
+///     .unwrap();
+///
+/// assert!(minigrep::run_collect(&config).is_err());
+/// ```
+pub fn run_collect(config: &Config) -> Result<Vec<String>, MinigrepError> {
+    let mut results = Vec::new();
+    search_files(config, &mut results)?;
+
+    Ok(results)
+}
+
+/// Counts the matching lines of every file named in a Config struct, or
+/// the lines that don't match in invert mode, pairing each file name with
+/// its count in the order the files are searched. Unlike `-c`, nothing is
+/// formatted or printed, and files without matches are counted as 0, as
+/// are files skipped for not being valid UTF-8.
+///
+/// ## Errors
+/// Fails with `Regex` when the query isn't a valid regular expression,
+/// with `UnmatchedPattern` for a glob pattern with no files, and with
+/// `FileNotFound` or `Io` for the first file that can't be read.
+///
+/// ## Example
+/// ```
+/// let config = minigrep::ConfigBuilder::new()
+///     .query("NAME")
+///     .filename("Cargo.toml")
+///     .case_sensitive(false)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(vec![("Cargo.toml".to_string(), 1)],
+///     minigrep::count_matches_per_file(&config).unwrap());
+/// ```
+pub fn count_matches_per_file(config: &Config) -> Result<Vec<(String, usize)>, MinigrepError> {
+    let mut failed = None;
+    let inputs = expand_inputs(config, &mut failed, &mut 0)?;
+    if let Some(e) = failed {
+        return Err(e);
+    }
+    let search = Search::new(config, false)?;
+    let lossy = config.binary_files.is_lossy();
+
+    inputs
+        .into_iter()
+        .map(|input| {
+            let result = open_input(config, &input.name)
+                .and_then(|reader| read_contents(reader, lossy))
+                .map(|contents| {
+                    let lines: Vec<&str> = records(&contents, terminator(config)).collect();
+                    matching_lines(&search, &contents, &lines).len()
+                });
+            let count = skip_undecodable(&search, &input, result)
+                .map_err(|e| MinigrepError::for_file(&input.name, e))?;
+            Ok((input.name, count))
+        })
+        .collect()
+}
+
+/// Whether standard input is the only input and nothing printed depends
+/// on more than the current line, so matches can be written as they come.
+fn searches_stdin_live(config: &Config) -> bool {
+    config.filenames == [STDIN_FILENAME]
+        && config.format == OutputFormat::Text
+        && config.before_context == 0
+        && config.after_context == 0
+        && !(config.count_only
+            || config.span
+            || config.quiet
+            || config.files_with_matches
+            || config.files_without_match
+            || config.multiline
+            || config.paragraph
+            || config.block
+            || config.group
+            || config.stats
+            || config.verbose
+            || config.decompress
+            || config.sort != SortOrder::None
+            || config.join.is_some()
+            || config.encoding.is_some())
+}
+
+/// Searches standard input a line at a time, writing and flushing every
+/// matching line to `writer` as soon as it's found.
+fn search_stdin_live<W: Write>(config: &Config, writer: &mut W) -> Result<bool, MinigrepError> {
+    let search = Search::new(config, config.with_filename == Some(true))?;
+    let input = Input {
+        name: STDIN_FILENAME.to_string(),
+        walked: None,
+    };
+    let terminator = terminator(config) as char;
+    let lossy = config.binary_files.is_lossy();
+
+    let mut failed_write = None;
+    let reader = io::BufReader::new(io::stdin().lock());
+    let result = search_stream(&search, STDIN_FILENAME, reader, lossy, |lines| {
+        let written = lines
+            .drain(..)
+            .try_for_each(|line| write!(writer, "{}{}", line, terminator))
+            .and_then(|()| writer.flush());
+        match written {
+            Ok(()) => true,
+            Err(e) => {
+                failed_write = Some(e);
+                false
+            }
+        }
+    });
+    if let Some(e) = failed_write {
+        return Err(e.into());
+    }
+    if search.timed_out() {
+        return Err(MinigrepError::Timeout(config.timeout.unwrap_or_default()));
+    }
+
+    match skip_undecodable(&search, &input, result) {
+        Ok(output) => Ok(output.match_count > 0),
+        Err(e) => {
+            eprintln!("{}: {}", display_name(STDIN_FILENAME), e);
+            Err(MinigrepError::for_file(STDIN_FILENAME, e))
+        }
+    }
+}
+
+/// What to do with files that aren't valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryFiles {
+    /// Skip them, with a warning on stderr for files that were named
+    /// directly rather than found in a recursive walk.
+    Skip,
+    /// Search them as text anyway, like grep's `--binary-files=text`,
+    /// with every invalid sequence replaced by U+FFFD the same as `Lossy`.
+    Text,
+    /// Search them with every invalid sequence replaced by U+FFFD.
+    Lossy,
+}
+
+impl BinaryFiles {
+    /// Whether files are decoded with invalid sequences replaced, rather
+    /// than left out.
+    fn is_lossy(self) -> bool {
+        self != BinaryFiles::Skip
+    }
+}
+
+/// A matching line as it is serialized in JSON output.
+#[derive(Serialize)]
+struct JsonMatch<'a> {
+    file: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line_number: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    byte_offset: Option<usize>,
+    text: &'a str,
+}
+
+/// Pushes the formatted result lines for every file onto `results`, so
+/// the lines gathered before a failure are kept, and returns whether any
+/// line matched. The files are searched as they are for `execute`, and
+/// the stats line is its report.
+///
+/// In JSON mode the matches of every file are gathered into one array,
+/// which is pushed as a single line once all files are searched. Context
+/// lines aren't included in it.
+///
+/// In quiet mode the files are searched one after another, stopping at
+/// the first match even if an earlier file failed.
+///
+/// With `progress` set, the number of files searched so far and the last
+/// of them are shown on a line of stderr that's redrawn a few times a
+/// second while the files are searched, and cleared before any errors are
+/// reported. It isn't shown in quiet mode.
+///
+/// In unique mode a line that's the same as one picked earlier in its
+/// file is left out, and not counted, going by its exact text after any
+/// whitespace trimming.
+///
+/// When the search times out, a `Timeout` error is returned, and with
+/// `partial` set the lines of the files searched before the file it was
+/// on are kept. Without it no lines are pushed.
+///
+/// Files found by walking a directory that can't be read, because
+/// permission is denied or they were removed after the walk, are skipped
+/// with a warning on stderr instead of failing the search, and counted in
+/// the stats.
+///
+/// With `match_filename` set, a file found by walking a directory whose
+/// path below it matches the query is reported on a line of its own,
+/// before its lines, and counted as a match.
+///
+/// With `join` set, the lines of every file are joined into one, with
+/// the separator between them, unless there are none.
+///
+/// In group mode the lines aren't prefixed with the file name. Instead,
+/// every file with output gets a block of lines headed by its name, and
+/// the blocks are separated by blank lines.
+fn search_files(config: &Config, results: &mut Vec<String>) -> Result<bool, MinigrepError> {
+    let started = Instant::now();
+    let first_result = results.len();
+    let mut failed = None;
+    let (mut report, json) = gather(config, &mut failed)?;
+
+    if config.quiet {
+        return match failed {
+            Some(e) if report.total_matches == 0 => Err(e),
+            _ => Ok(report.total_matches > 0),
+        };
+    }
+
+    for file in std::mem::take(&mut report.files) {
+        if config.group && !file.lines.is_empty() {
+            if !results.is_empty() {
+                results.push(String::new());
+            }
+            results.push(file.name);
+        }
+        results.extend(file.lines);
+    }
+
+    if let Some(separator) = &config.join {
+        if results.len() > first_result {
+            let joined = results.split_off(first_result).join(separator);
+            results.push(joined);
+        }
+    }
+
+    let json_output = config.format == OutputFormat::Json;
+    if json_output {
+        results.push(serde_json::to_string(&json)?);
+    }
+
+    if config.stats && json_output {
+        eprintln!("{}", report);
+    } else if config.stats {
+        results.push(report.to_string());
+    }
+
+    if config.verbose {
+        eprintln!(
+            "scanned {} {}, {} matched in {:.3}s",
+            report.lines_scanned,
+            plural(report.lines_scanned, "line", "lines"),
+            report.total_matches,
+            started.elapsed().as_secs_f64()
+        );
+    }
+
+    if let Some(e) = failed {
+        if matches!(e, MinigrepError::Timeout(_)) && !config.partial {
+            results.truncate(first_result);
+        }
+        return Err(e);
+    }
+
+    Ok(report.total_matches > 0)
+}
+
+/// Searches the files named in a Config struct and reports what was found
+/// in each of them, with the totals over all of them, without printing
+/// anything. The lines of each file are formatted the way `run` prints
+/// them, except that they're never grouped or joined, and that they're
+/// left out in JSON format. In quiet mode the search stops at the first
+/// file with a match.
+///
+/// ## Errors
+/// Fails in the same situations as `run`, with the first error, after
+/// searching every file.
+///
+/// ## Example
+/// ```
+/// let args = ["minigrep", "-s", "name", "Cargo.toml"];
+/// let config = minigrep::Config::new(args.iter().map(|arg| arg.to_string())).unwrap();
+/// let report = minigrep::execute(&config).unwrap();
+///
+/// assert_eq!(1, report.files_searched);
+/// assert_eq!(1, report.files_with_matches);
+/// assert_eq!(report.total_matches, report.files[0].match_count);
+/// assert_eq!("1 match in 1 file (1 file searched)", report.to_string());
+/// ```
+pub fn execute(config: &Config) -> Result<SearchReport, MinigrepError> {
+    let mut failed = None;
+    let (report, _) = gather(config, &mut failed)?;
+
+    failed.map_or(Ok(report), Err)
+}
+
+/// Searches the files for a report, along with the JSON objects of their
+/// matches. Files that fail are reported on stderr as they're found, with
+/// the first error kept in `failed`, without stopping the search, unless
+/// it times out.
+///
+/// ## Errors
+/// Fails when the search can't start at all, like for an invalid query.
+fn gather(
+    config: &Config,
+    failed: &mut Option<MinigrepError>,
+) -> Result<(SearchReport, Vec<serde_json::Value>), MinigrepError> {
+    let mut skipped = 0;
+    let inputs = expand_inputs(config, failed, &mut skipped)?;
+    let several = inputs.len() > 1 || inputs.iter().any(|input| input.walked.is_some());
+    let mut search = Search::new(
+        config,
+        !config.group && config.with_filename.unwrap_or(several),
+    )?;
+    search.names = relative_names(config, &inputs, failed);
+    let mut report = SearchReport {
+        files_skipped: skipped,
+        ..SearchReport::default()
+    };
+    let mut json = Vec::new();
+
+    if config.quiet {
+        for input in &inputs {
+            let output = match filename_match(&search, input) {
+                Some(_) => Ok(FileOutput {
+                    match_count: 1,
+                    ..FileOutput::default()
+                }),
+                None => search_file(&search, input),
+            };
+            match output {
+                Ok(output) => {
+                    let matched = output.match_count > 0;
+                    report.add(&search, input, output);
+                    if matched {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::TimedOut && search.timed_out() => {
+                    *failed = Some(MinigrepError::Timeout(config.timeout.unwrap_or_default()));
+                    break;
+                }
+                Err(e) if is_skippable(input, &e) => {
+                    warn_skipped(input, &e);
+                    report.files_skipped += 1;
+                }
+                Err(e) => {
+                    eprintln!("{}: {}", display_name(&input.name), e);
+                    failed.get_or_insert(MinigrepError::for_file(&input.name, e));
+                }
+            }
+        }
+
+        return Ok((report, json));
+    }
+
+    let outputs = search_inputs(&search, &inputs);
+    if let Some(progress) = &search.progress {
+        progress.finish();
+    }
+
+    for (input, output) in inputs.iter().zip(outputs) {
+        match output {
+            Ok(mut output) => {
+                if let Some(line) = filename_match(&search, input) {
+                    output.lines.insert(0, line);
+                    output.match_count += 1;
+                }
+                json.append(&mut output.json);
+                report.add(&search, input, output);
+            }
+            Err(e) if e.kind() == io::ErrorKind::TimedOut && search.timed_out() => {
+                *failed = Some(MinigrepError::Timeout(config.timeout.unwrap_or_default()));
+                break;
+            }
+            Err(e) if is_skippable(input, &e) => {
+                warn_skipped(input, &e);
+                report.files_skipped += 1;
+            }
+            Err(e) => {
+                eprintln!("{}: {}", display_name(&input.name), e);
+                failed.get_or_insert(MinigrepError::for_file(&input.name, e));
+            }
+        }
+    }
+
+    Ok((report, json))
+}
+
+/// What a search of the files named in a Config struct found, as returned
+/// by `execute`. It's displayed as the line printed in stats mode.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchReport {
+    /// How many files were searched, leaving out the ones that couldn't
+    /// be read.
+    pub files_searched: usize,
+    /// How many of them had at least one match.
+    pub files_with_matches: usize,
+    /// How many lines matched over all the files, or didn't in invert
+    /// mode. Modes that stop at a file's first match count one per file.
+    pub total_matches: usize,
+    /// How many lines were looked at.
+    pub lines_scanned: usize,
+    /// How many files found by walking a directory couldn't be read.
+    pub files_skipped: usize,
+    /// Every file searched, in the order they were searched in.
+    pub files: Vec<FileReport>,
+}
+
+/// What a search found in one file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileReport {
+    /// The name the file is shown under in the output.
+    pub name: String,
+    /// How many lines of the file matched.
+    pub match_count: usize,
+    /// The lines printed for the file, with their prefixes.
+    pub lines: Vec<String>,
+}
+
+impl SearchReport {
+    /// Adds a file's output to the report and its totals.
+    fn add(&mut self, search: &Search, input: &Input, output: FileOutput) {
+        self.files_searched += 1;
+        if output.match_count > 0 {
+            self.files_with_matches += 1;
+        }
+        self.total_matches += output.match_count;
+        self.lines_scanned += output.lines_scanned;
+        self.files.push(FileReport {
+            name: search.display_name(&input.name).to_string(),
+            match_count: output.match_count,
+            lines: output.lines,
+        });
+    }
+}
+
+impl fmt::Display for SearchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} in {} {} ({} {} searched",
+            self.total_matches,
+            plural(self.total_matches, "match", "matches"),
+            self.files_with_matches,
+            plural(self.files_with_matches, "file", "files"),
+            self.files_searched,
+            plural(self.files_searched, "file", "files"),
+        )?;
+        if self.files_skipped > 0 {
+            write!(f, ", {} skipped", self.files_skipped)?;
+        }
+        write!(f, ")")
+    }
+}
+
+fn plural<'a>(count: usize, one: &'a str, many: &'a str) -> &'a str {
+    if count == 1 {
+        one
+    } else {
+        many
+    }
+}
+
+/// What every file's search shares.
+struct Search<'a> {
+    config: &'a Config,
+    matcher: Matcher,
+    /// What matches the lines that are left out despite matching.
+    exclude: Option<Matcher>,
+    show_filenames: bool,
+    colored: bool,
+    /// When the search times out, if it can.
+    deadline: Option<Instant>,
+    /// How far the search has got, when that's shown.
+    progress: Option<Progress>,
+    /// The lines' leading timestamps must be at or after this.
+    since: Option<Vec<u32>>,
+    /// The lines' leading timestamps must be at or before this.
+    until: Option<Vec<u32>>,
+    /// What matches the lines that head sections.
+    heading: Option<Regex>,
+    /// The names files are shown under, by the names they're opened by,
+    /// when they differ.
+    names: HashMap<String, String>,
+}
+
+/// How many times a second the progress line is redrawn at most.
+const PROGRESS_UPDATES_PER_SECOND: u32 = 4;
+
+/// The progress line shown on stderr, with the number of files searched
+/// and the last of them, which is redrawn over itself as files are done.
+struct Progress {
+    searched: AtomicUsize,
+    /// When the line was last drawn, if it has been.
+    drawn: Mutex<Option<Instant>>,
+}
+
+impl Progress {
+    fn new() -> Progress {
+        Progress {
+            searched: AtomicUsize::new(0),
+            drawn: Mutex::new(None),
+        }
+    }
+
+    /// Counts the file as searched, redrawing the line unless it was drawn
+    /// too recently or another thread is drawing it.
+    fn searched(&self, filename: &str) {
+        let searched = self.searched.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut drawn = match self.drawn.try_lock() {
+            Ok(drawn) => drawn,
+            Err(_) => return,
+        };
+        let interval = Duration::from_secs(1) / PROGRESS_UPDATES_PER_SECOND;
+        if drawn.is_some_and(|drawn| drawn.elapsed() < interval) {
+            return;
+        }
+
+        eprint!(
+            "\r\x1b[Ksearched {} {}: {}",
+            searched,
+            plural(searched, "file", "files"),
+            display_name(filename)
+        );
+        *drawn = Some(Instant::now());
+    }
+
+    /// Clears the line, if it was ever drawn, so it doesn't stay behind
+    /// the shell prompt or what's printed next on stderr.
+    fn finish(&self) {
+        if self.drawn.lock().is_ok_and(|drawn| drawn.is_some()) {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+impl Search<'_> {
+    /// Prepares the search for the config, failing when its query doesn't
+    /// make a valid matcher.
+    fn new(config: &Config, show_filenames: bool) -> Result<Search<'_>, MinigrepError> {
+        Ok(Search {
+            config,
+            matcher: Matcher::new(config)?,
+            exclude: config
+                .exclude_pattern
+                .as_deref()
+                .map(|pattern| Matcher::exclusion(config, pattern))
+                .transpose()?,
+            show_filenames,
+            // `run` has already decided Auto for stdout, and any other
+            // writer may not be a terminal.
+            colored: config.color == ColorChoice::Always,
+            deadline: config.timeout.map(|timeout| Instant::now() + timeout),
+            // Quiet mode prints nothing at all.
+            progress: (config.progress && !config.quiet).then(Progress::new),
+            since: config.since.as_deref().map(time_bound).transpose()?,
+            until: config.until.as_deref().map(time_bound).transpose()?,
+            heading: config
+                .heading_pattern
+                .as_deref()
+                .map(Regex::new)
+                .transpose()?,
+            names: HashMap::new(),
+        })
+    }
+
+    /// Prepares a search of contents already in memory, which never fails:
+    /// a query or exclude pattern that isn't a valid regular expression is
+    /// matched as plain text, and invalid timestamps are ignored.
+    fn lenient(config: &Config) -> Search<'_> {
+        Search {
+            config,
+            matcher: Matcher::new(config).unwrap_or_else(|_| Matcher::literal(config)),
+            exclude: config.exclude_pattern.as_deref().map(|pattern| {
+                Matcher::exclusion(config, pattern)
+                    .unwrap_or_else(|_| Matcher::plain(pattern, config.searches_case_sensitively()))
+            }),
+            show_filenames: false,
+            colored: false,
+            deadline: None,
+            progress: None,
+            since: config.since.as_deref().and_then(parse_timestamp),
+            until: config.until.as_deref().and_then(parse_timestamp),
+            // Nothing is printed, so headings don't matter.
+            heading: None,
+            names: HashMap::new(),
+        }
+    }
+
+    /// The name the file is shown under in the output.
+    fn display_name<'s>(&'s self, filename: &'s str) -> &'s str {
+        match self.names.get(filename) {
+            Some(name) => name,
+            None => display_name(filename),
+        }
+    }
+
+    /// Whether the line heads a section.
+    fn is_heading(&self, line: &str) -> bool {
+        self.heading.as_ref().is_some_and(|re| re.is_match(line))
+    }
+
+    /// Whether the search has run past its timeout.
+    fn timed_out(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Whether a loop that's on the line with the given index should stop
+    /// because the search timed out. The clock is only looked at every
+    /// `TIMEOUT_CHECK_INTERVAL` lines.
+    fn timed_out_at(&self, index: usize) -> bool {
+        index.is_multiple_of(TIMEOUT_CHECK_INTERVAL) && self.timed_out()
+    }
+
+    /// Whether the line is one the search picks out: one that matches, or
+    /// one that doesn't in invert mode. Blank lines are never picked in
+    /// skip-blank mode.
+    fn selects(&self, line: &str) -> bool {
+        !self.skips(line) && self.is_match(line) != self.config.invert_match
+    }
+
+    /// The text that tells lines apart in unique mode: the exact line, or
+    /// the line without surrounding whitespace when that's ignored.
+    fn unique_text<'l>(&self, line: &'l str) -> &'l str {
+        if self.config.ignore_whitespace {
+            line.trim()
+        } else {
+            line
+        }
+    }
+
+    /// Whether the line is left out of the output whatever it holds: a
+    /// blank one in skip-blank mode, or one outside the time window.
+    fn skips(&self, line: &str) -> bool {
+        if self.config.skip_blank && line.trim().is_empty() {
+            return true;
+        }
+        (self.since.is_some() || self.until.is_some())
+            && !in_time_window(
+                line,
+                self.since.as_deref(),
+                self.until.as_deref(),
+                self.config.keep_untimed,
+            )
+    }
+
+    /// Whether the line matches, looking only at the chosen field when
+    /// there is one. Lines without that field never match.
+    fn is_match(&self, line: &str) -> bool {
+        let stripped;
+        let line = if self.config.strip_ansi && line.contains('\x1b') {
+            stripped = strip_ansi(line);
+            stripped.as_str()
+        } else {
+            line
+        };
+        match field_range(self.config, line) {
+            Some(range) => self.matcher.is_match(&line[range]) && !self.excludes(line),
+            None => false,
+        }
+    }
+
+    /// Whether the line, or its chosen field, matches the exclude pattern.
+    fn excludes(&self, line: &str) -> bool {
+        match (&self.exclude, field_range(self.config, line)) {
+            (Some(exclude), Some(range)) => exclude.is_match(&line[range]),
+            _ => false,
+        }
+    }
+
+    /// Byte ranges of every non-empty match in the line, or in its
+    /// chosen field, relative to the whole line.
+    fn find_ranges(&self, line: &str) -> Vec<Range<usize>> {
+        // Lines printed as they were still match without their escape
+        // sequences, so the matches are found in the stripped line and
+        // mapped back onto the original.
+        if self.config.strip_ansi && line.contains('\x1b') {
+            let (stripped, offsets) = strip_ansi_mapped(line);
+            return self
+                .find_ranges(&stripped)
+                .into_iter()
+                .map(|range| {
+                    let start = offsets[range.start];
+                    if range.is_empty() {
+                        start..start
+                    } else {
+                        start..offsets[range.end - 1] + 1
+                    }
+                })
+                .collect();
+        }
+        match field_range(self.config, line) {
+            Some(range) => self
+                .matcher
+                .find_ranges(&line[range.clone()])
+                .into_iter()
+                .map(|found| found.start + range.start..found.end + range.start)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The line with every match replaced by the `replace` text, where
+    /// `$1` or `${name}` stand for a capture group in regex mode.
+    fn replace_line<'l>(&self, line: &'l str) -> Cow<'l, str> {
+        let (replacement, range) = match (&self.config.replace, field_range(self.config, line)) {
+            (Some(replacement), Some(range)) => (replacement, range),
+            _ => return Cow::Borrowed(line),
+        };
+
+        let field = &line[range.clone()];
+        let replaced = match &self.matcher {
+            Matcher::Regex(re) => re.replace_all(field, replacement.as_str()),
+            matcher => splice(field, &matcher.find_ranges(field), replacement),
+        };
+
+        match replaced {
+            Cow::Borrowed(_) => Cow::Borrowed(line),
+            Cow::Owned(replaced) => Cow::Owned(format!(
+                "{}{}{}",
+                &line[..range.start],
+                replaced,
+                &line[range.end..]
+            )),
+        }
+    }
+}
+
+/// The text with each of the sorted, non-overlapping byte ranges replaced
+/// by the replacement.
+fn splice<'t>(text: &'t str, ranges: &[Range<usize>], replacement: &str) -> Cow<'t, str> {
+    if ranges.is_empty() {
+        return Cow::Borrowed(text);
+    }
+
+    let mut spliced = String::with_capacity(text.len());
+    let mut last = 0;
+    for range in ranges {
+        spliced.push_str(&text[last..range.start]);
+        spliced.push_str(replacement);
+        last = range.end;
+    }
+    spliced.push_str(&text[last..]);
+
+    Cow::Owned(spliced)
+}
+
+/// The byte range of the part of the line that's searched: the field
+/// chosen with `--field`, if the line has it, or else the whole line,
+/// without its surrounding whitespace when that's ignored.
+fn field_range(config: &Config, line: &str) -> Option<Range<usize>> {
+    let range = match config.field {
+        Some(number) => {
+            let mut start = 0;
+            for _ in 1..number {
+                start += line[start..].find(config.delimiter.as_str())? + config.delimiter.len();
+            }
+            let end = line[start..]
+                .find(config.delimiter.as_str())
+                .map_or(line.len(), |at| start + at);
+            start..end
+        }
+        None => 0..line.len(),
+    };
+
+    if !config.ignore_whitespace {
+        return Some(range);
+    }
+    let searched = &line[range.clone()];
+    let start = range.start + searched.len() - searched.trim_start().len();
+    Some(start..start + searched.trim().len())
+}
+
+/// Everything one file contributes to the output.
+#[derive(Default)]
+struct FileOutput {
+    lines: Vec<String>,
+    json: Vec<serde_json::Value>,
+    /// How many lines of the file matched. Modes that stop at the first
+    /// match count at most one.
+    match_count: usize,
+    /// How many lines were looked at before the search of the file was
+    /// done with it.
+    lines_scanned: usize,
+}
+
+/// Searches every input, spreading them over worker threads when there
+/// is more than one, and returns the outputs in the same order as the
+/// inputs.
+fn search_inputs(search: &Search, inputs: &[Input]) -> Vec<io::Result<FileOutput>> {
+    let threads = search
+        .config
+        .threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .min(inputs.len());
+
+    if threads <= 1 {
+        return inputs
+            .iter()
+            .map(|input| search_file(search, input))
+            .collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut outputs: Vec<(usize, io::Result<FileOutput>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut outputs = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        match inputs.get(index) {
+                            Some(input) => outputs.push((index, search_file(search, input))),
+                            None => return outputs,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("search thread panicked"))
+            .collect()
+    });
+
+    outputs.sort_by_key(|(index, _)| *index);
+    outputs.into_iter().map(|(_, output)| output).collect()
+}
+
+/// What every line of a block starts with in block mode.
+const BLOCK_INDENT: &str = "    ";
+
+/// How many lines are scanned between looks at the clock when there's a
+/// timeout.
+const TIMEOUT_CHECK_INTERVAL: usize = 1024;
+
+/// The error a file's search fails with when the search times out.
+fn timed_out_error() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "the search timed out")
+}
+
+/// Searches a single file like `scan_file`, unless the search has timed
+/// out. A timeout while the file is scanned makes it fail too, since its
+/// output may be incomplete, and in-place mode leaves it untouched.
+fn search_file(search: &Search, input: &Input) -> io::Result<FileOutput> {
+    if search.timed_out() {
+        return Err(timed_out_error());
+    }
+    let output = scan_file(search, input);
+    if let Some(progress) = &search.progress {
+        progress.searched(&input.name);
+    }
+    let output = output?;
+    if search.timed_out() {
+        return Err(timed_out_error());
+    }
+
+    Ok(output)
+}
+
+/// The line reporting the input because its path below the walked
+/// directory matches the query, when names are matched and it was found
+/// by walking one. The directory itself isn't matched, so it doesn't make
+/// every file underneath it match. The matches are highlighted when color
+/// is on.
+fn filename_match(search: &Search, input: &Input) -> Option<String> {
+    let walked = input.walked.as_deref()?;
+    if !search.config.match_filename || !search.matcher.is_match(walked) {
+        return None;
+    }
+
+    let name = search.display_name(&input.name);
+    let shown = match name.strip_suffix(walked) {
+        Some(root) if search.colored => format!(
+            "{}{}",
+            root,
+            highlight(
+                walked,
+                &search.matcher.find_ranges(walked),
+                &search.config.match_color,
+            )
+        ),
+        _ => name.to_string(),
+    };
+    Some(format!("{}{}", shown, FILENAME_MATCH_MARKER))
+}
+
+/// Whether the failure to search the input only skips it: it was found
+/// by walking a directory, and can't be read because permission is denied
+/// or it was removed after the walk.
+fn is_skippable(input: &Input, e: &io::Error) -> bool {
+    input.walked.is_some() && is_unreadable(e)
+}
+
+/// Whether the error is one that skips a file met while walking a
+/// directory: permission is denied, or it's gone, like the target of a
+/// broken symlink.
+fn is_unreadable(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::PermissionDenied | io::ErrorKind::NotFound
+    )
+}
+
+fn warn_skipped(input: &Input, e: &io::Error) {
+    eprintln!(
+        "{}: skipping file that can't be read: {}",
+        display_name(&input.name),
+        e
+    );
+}
+
+/// Files larger than this many bytes are always searched line by line
+/// rather than being read into memory whole.
+pub const STREAM_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// Searches a single file and formats its results. Files that aren't
+/// valid UTF-8 are handled according to `binary_files`.
+///
+/// When only the names of files with (or without) matches are wanted, or
+/// only whether the file matches at all, the search stops at the first
+/// match.
+///
+/// The file is streamed line by line when streaming is asked for or the
+/// file is larger than `STREAM_THRESHOLD`, unless context lines are
+/// needed, which requires the whole file.
+fn scan_file(search: &Search, input: &Input) -> io::Result<FileOutput> {
+    let config = search.config;
+    let filename = input.name.as_str();
+    let lossy = config.binary_files.is_lossy();
+
+    if config.in_place {
+        let result = replace_in_place(search, filename, lossy);
+        return skip_undecodable(search, input, result);
+    }
+
+    if config.files_with_matches || config.files_without_match || config.quiet {
+        let result = with_retries(config, filename, || file_has_match(search, filename, lossy));
+        let result = result.map(|(found, lines_scanned)| {
+            let mut output = FileOutput {
+                match_count: found as usize,
+                lines_scanned,
+                ..FileOutput::default()
+            };
+            // Quiet mode lists no files at all.
+            if (config.files_with_matches && found) || (config.files_without_match && !found) {
+                output.lines.push(search.display_name(filename).to_string());
+            }
+            output
+        });
+
+        return skip_undecodable(search, input, result);
+    }
+
+    let streaming = config.before_context == 0
+        && config.after_context == 0
+        && !config.multiline
+        && !config.paragraph
+        && !config.block
+        && config.sort == SortOrder::None
+        && (config.stream
+            || config.line_range.is_some()
+            || config.lines.is_some()
+            || (filename != STDIN_FILENAME && fs::metadata(filename)?.len() > STREAM_THRESHOLD));
+
+    let result = with_retries(config, filename, || {
+        if config.mmap
+            && filename != STDIN_FILENAME
+            && !is_compressed(config, filename)
+            && config.encoding.is_none()
+        {
+            search_mapped(search, filename, lossy)
+        } else if streaming {
+            let reader = io::BufReader::new(open_input(config, filename)?);
+            search_stream(search, filename, reader, lossy, |_| true)
+        } else {
+            read_contents(open_input(config, filename)?, lossy)
+                .map(|contents| search_contents(search, filename, &contents))
+        }
+    });
+
+    skip_undecodable(search, input, result)
+}
+
+/// How long to wait before the first retry of a read that failed. Each
+/// retry waits twice as long as the one before, up to `MAX_RETRY_DELAY`.
+const RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// The longest wait between retries.
+const MAX_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Calls `read` until it succeeds, fails in a way that isn't transient,
+/// or has been retried as many times as the config allows, sleeping a
+/// little longer between each attempt. Standard input can't be read
+/// again, so it's never retried.
+fn with_retries<T, F>(config: &Config, filename: &str, read: F) -> io::Result<T>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    let retries = match config.retries {
+        Some(retries) if filename != STDIN_FILENAME => retries,
+        _ => 0,
+    };
+    retry(retries, read)
+}
+
+fn retry<T, F>(retries: usize, mut read: F) -> io::Result<T>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    let mut delay = RETRY_DELAY;
+    let mut attempts = 0;
+    loop {
+        match read() {
+            Err(e) if attempts < retries && is_transient(&e) => {
+                thread::sleep(delay);
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+                attempts += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether reading again might work: anything but a missing file, a
+/// denied permission, undecodable contents or a timed out search.
+fn is_transient(e: &io::Error) -> bool {
+    !matches!(
+        e.kind(),
+        io::ErrorKind::NotFound
+            | io::ErrorKind::PermissionDenied
+            | io::ErrorKind::InvalidData
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::TimedOut
+    )
+}
+
+/// Turns the failure to decode a file into empty output when such files
+/// are to be skipped, warning about files that were named directly.
+fn skip_undecodable<T: Default>(
+    search: &Search,
+    input: &Input,
+    result: io::Result<T>,
+) -> io::Result<T> {
+    match result {
+        Err(e)
+            if search.config.binary_files == BinaryFiles::Skip
+                && e.kind() == io::ErrorKind::InvalidData =>
+        {
+            if input.walked.is_none() {
+                eprintln!(
+                    "{}: skipping file that isn't valid UTF-8",
+                    display_name(&input.name)
+                );
+            }
+            Ok(T::default())
+        }
+        result => result,
+    }
+}
+
+/// Whether any line of the file matches, stopping at the first one, and
+/// how many lines were looked at.
+fn file_has_match(search: &Search, filename: &str, lossy: bool) -> io::Result<(bool, usize)> {
+    if search.config.multiline {
+        let contents = read_contents(open_input(search.config, filename)?, lossy)?;
+        let lines: Vec<&str> = records(&contents, terminator(search.config)).collect();
+        let matched = matching_lines(search, &contents, &lines);
+        return Ok((
+            !matched.is_empty(),
+            lines_scanned(search.config, lines.len(), &matched),
+        ));
+    }
+
+    let (first, last) = line_indices(search.config);
+    let mut found = false;
+    let mut scanned = 0;
+    for_each_line(
+        io::BufReader::new(open_input(search.config, filename)?),
+        terminator(search.config),
+        lossy,
+        |at, line| {
+            if at.index < first {
+                return true;
+            }
+            if search.timed_out_at(at.index) {
+                return false;
+            }
+            scanned += 1;
+            found = is_listed(search.config, at.index) && search.selects(line);
+            !found && at.index < last
+        },
+    )?;
+
+    Ok((found, scanned))
+}
+
+/// How many of a file's lines `matching_lines` looked at before it found
+/// the lines it returned: every line in the line range, unless it hit
+/// the maximum count and stopped at the last match.
+fn lines_scanned(config: &Config, line_count: usize, matched: &[usize]) -> usize {
+    let (first, last) = line_indices(config);
+    match matched.last() {
+        Some(&index) if Some(matched.len()) == config.max_count => index + 1 - first,
+        _ => line_count.min(last.saturating_add(1)).saturating_sub(first),
+    }
+}
+
+/// The 0-based indices of the first and last lines to search, which are
+/// all of them unless there's a line range or a list of lines.
+fn line_indices(config: &Config) -> (usize, usize) {
+    let (first, last) = match config.line_range {
+        Some((start, end)) => (start.saturating_sub(1), end.saturating_sub(1)),
+        None => (0, usize::MAX),
+    };
+    match config.lines.as_deref() {
+        Some([(lowest, _), .., (_, highest)]) | Some([(lowest, highest)]) => (
+            first.max(lowest.saturating_sub(1)),
+            last.min(highest.saturating_sub(1)),
+        ),
+        _ => (first, last),
+    }
+}
+
+/// Whether the line with the 0-based index is one of the listed lines,
+/// which every line is when there's no list.
+fn is_listed(config: &Config, index: usize) -> bool {
+    config.lines.as_ref().is_none_or(|lines| {
+        let number = index + 1;
+        lines
+            .binary_search_by(|&(start, end)| {
+                if end < number {
+                    std::cmp::Ordering::Less
+                } else if start > number {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    })
+}
+
+/// The indices of the lines that match, or that don't in invert mode, up
+/// to the maximum count and within the line range. In multiline mode a
+/// line matches when any match of the regular expression across the whole
+/// contents touches it.
+fn matching_lines(search: &Search, contents: &str, lines: &[&str]) -> Vec<usize> {
+    let config = search.config;
+    let limit = config.max_count.unwrap_or(usize::MAX);
+    let (first, last) = line_indices(config);
+    let last = last.min(lines.len().saturating_sub(1));
+
+    let mut seen = HashSet::new();
+    let re = match &search.matcher {
+        Matcher::Regex(re) if config.multiline => re,
+        _ => {
+            return lines
+                .iter()
+                .enumerate()
+                .take(last + 1)
+                .skip(first)
+                .take_while(|&(index, _)| !search.timed_out_at(index))
+                .filter(|&(index, line)| is_listed(config, index) && search.selects(line))
+                .filter(|(_, line)| !config.unique || seen.insert(search.unique_text(line)))
+                .map(|(index, _)| index)
+                .take(limit)
+                .collect()
+        }
+    };
+
+    let starts = line_starts(contents, terminator(config));
+    let mut touched = vec![false; lines.len()];
+    for found in re.find_iter(contents) {
+        let first = line_of(&starts, found.start());
+        let last = line_of(&starts, found.end().saturating_sub(1).max(found.start()));
+        // An empty match after the final line break touches no line.
+        if let Some(span) = touched.get_mut(first..=last.min(lines.len().saturating_sub(1))) {
+            span.fill(true);
+        }
+    }
+
+    touched
+        .into_iter()
+        .enumerate()
+        .take(last + 1)
+        .skip(first)
+        .filter(|&(index, touched)| {
+            (touched && !search.excludes(lines[index])) != config.invert_match
+                && is_listed(config, index)
+                && !search.skips(lines[index])
+                && (!config.unique || seen.insert(search.unique_text(lines[index])))
+        })
+        .map(|(index, _)| index)
+        .take(limit)
+        .collect()
+}
+
+/// The parts of every line that the matches of the regular expression
+/// across the whole contents cover, as ranges within the line.
+fn multiline_ranges(
+    re: &Regex,
+    contents: &str,
+    starts: &[usize],
+    lines: &[&str],
+) -> Vec<Vec<Range<usize>>> {
+    let mut ranges = vec![Vec::new(); lines.len()];
+    for found in re.find_iter(contents).filter(|found| !found.is_empty()) {
+        let first = line_of(starts, found.start());
+        let last = line_of(starts, found.end() - 1).min(lines.len().saturating_sub(1));
+        for index in first..=last {
+            let start = found.start().max(starts[index]) - starts[index];
+            let end = found.end().min(starts[index] + lines[index].len()) - starts[index];
+            if start < end {
+                ranges[index].push(start..end);
+            }
+        }
+    }
+    ranges
+}
+
+/// The byte offset at which every line of the contents, ending in the
+/// terminator, starts.
+fn line_starts(contents: &str, terminator: u8) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(
+            contents
+                .match_indices(terminator as char)
+                .map(|(at, _)| at + 1),
+        )
+        .collect()
+}
+
+/// The index of the line holding the byte at `offset`.
+fn line_of(starts: &[usize], offset: usize) -> usize {
+    starts.partition_point(|&start| start <= offset) - 1
+}
+
+/// Searches a file read into memory whole.
+///
+/// In block mode every group of a match and its context lines is printed
+/// indented under a `file:line` header, with the number of the group's
+/// first line, and the groups are separated by blank lines.
+fn search_contents(search: &Search, filename: &str, contents: &str) -> FileOutput {
+    let config = search.config;
+    let stripped;
+    let contents = if prints_stripped(config) && contents.contains('\x1b') {
+        stripped = strip_ansi(contents);
+        stripped.as_str()
+    } else {
+        contents
+    };
+    if config.paragraph {
+        return search_paragraph_contents(search, filename, contents);
+    }
+    let prefix = file_prefix(search, filename);
+    let mut output = FileOutput::default();
+
+    let lines: Vec<&str> = records(contents, terminator(config)).collect();
+    let starts = line_starts(contents, terminator(config));
+    let at = |index| LinePosition {
+        index,
+        offset: starts[index],
+    };
+    let mut matched = matching_lines(search, contents, &lines);
+    output.match_count = matched.len();
+    output.lines_scanned = lines_scanned(config, lines.len(), &matched);
+
+    if config.count_only {
+        output.lines.push(format!("{}{}", prefix, matched.len()));
+        return output;
+    }
+    if config.span {
+        let span = matched.first().copied().zip(matched.last().copied());
+        output.lines.push(span_line(&prefix, span, matched.len()));
+        return output;
+    }
+
+    if let (true, true, Matcher::Regex(re)) =
+        (config.multiline, config.only_matching, &search.matcher)
+    {
+        for found in re.find_iter(contents).filter(|found| !found.is_empty()) {
+            let index = line_of(&starts, found.start());
+            if matched.binary_search(&index).is_ok() {
+                let at = LinePosition {
+                    index,
+                    offset: found.start(),
+                };
+                push_only_matching(search, &prefix, at, found.as_str(), &mut output);
+            }
+        }
+        return output;
+    }
+
+    // A match spanning lines can't be found again in any one of them, so
+    // the part of it on each line is highlighted instead.
+    let highlights = match &search.matcher {
+        Matcher::Regex(re) if config.multiline && search.colored => {
+            Some(multiline_ranges(re, contents, &starts, &lines))
+        }
+        _ => None,
+    };
+
+    match config.sort {
+        SortOrder::None => {}
+        SortOrder::Length => {
+            matched.sort_by_key(|&index| std::cmp::Reverse(lines[index].chars().count()))
+        }
+        SortOrder::Matches => {
+            matched.sort_by_key(|&index| std::cmp::Reverse(search.find_ranges(lines[index]).len()))
+        }
+    }
+
+    if config.format == OutputFormat::Json
+        || config.only_matching
+        || config.vimgrep
+        || config.sort != SortOrder::None
+    {
+        for &index in &matched {
+            push_match(
+                search,
+                filename,
+                &prefix,
+                at(index),
+                lines[index],
+                highlights.as_ref().map(|ranges| ranges[index].as_slice()),
+                &mut output,
+            );
+        }
+        return output;
+    }
+
+    let groups = context_groups(
+        &matched,
+        lines.len(),
+        config.before_context,
+        config.after_context,
+    );
+    let has_context = config.before_context > 0 || config.after_context > 0;
+    let headings: Vec<usize> = (0..lines.len())
+        .filter(|&index| search.is_heading(lines[index]))
+        .collect();
+    let heading_above = |index| {
+        let above = headings.partition_point(|&heading| heading < index);
+        headings[..above].last().copied()
+    };
+    let mut shown_heading = None;
+    let mut matched = matched.iter().peekable();
+    // Blocks show the file name in their headers instead.
+    let prefix = if config.block {
+        BLOCK_INDENT.to_string()
+    } else {
+        prefix
+    };
+
+    for (i, group) in groups.into_iter().enumerate() {
+        if config.block {
+            if i > 0 {
+                output.lines.push(String::new());
+            }
+            output.lines.push(format!(
+                "{}:{}",
+                search.display_name(filename),
+                group.start + 1
+            ));
+        } else if i > 0 && has_context {
+            output.lines.push("--".to_string());
+        }
+
+        // The heading of the group's first match goes above its context
+        // lines, unless it's one of them.
+        let first_heading = matched.peek().and_then(|&&first| heading_above(first));
+        if let Some(heading) = first_heading {
+            if heading < group.start && shown_heading != Some(heading) {
+                output
+                    .lines
+                    .push(heading_line(&prefix, heading, lines[heading]));
+                shown_heading = Some(heading);
+            }
+        }
+
+        for index in group {
+            if matched.next_if_eq(&&index).is_some() {
+                if let Some(heading) = heading_above(index) {
+                    if shown_heading != Some(heading) {
+                        output
+                            .lines
+                            .push(heading_line(&prefix, heading, lines[heading]));
+                        shown_heading = Some(heading);
+                    }
+                }
+                push_match(
+                    search,
+                    filename,
+                    &prefix,
+                    at(index),
+                    lines[index],
+                    highlights.as_ref().map(|ranges| ranges[index].as_slice()),
+                    &mut output,
+                );
+            } else if !search.skips(lines[index]) {
+                if headings.binary_search(&index).is_ok() {
+                    shown_heading = Some(index);
+                }
+                push_plain(config, &prefix, at(index), lines[index], &mut output);
+            }
+        }
+    }
+
+    output
+}
+
+/// Searches a file read into memory whole a paragraph at a time. Every
+/// line of a paragraph with a matching line is printed, or of one without
+/// any in invert mode, and the paragraphs are separated by blank lines.
+/// The maximum count and the match count are of paragraphs.
+fn search_paragraph_contents(search: &Search, filename: &str, contents: &str) -> FileOutput {
+    let config = search.config;
+    let prefix = file_prefix(search, filename);
+    let mut output = FileOutput::default();
+
+    let lines: Vec<&str> = records(contents, terminator(config)).collect();
+    let starts = line_starts(contents, terminator(config));
+    let limit = config.max_count.unwrap_or(usize::MAX);
+    output.lines_scanned = lines.len();
+
+    for paragraph in paragraphs(&lines) {
+        let matched = paragraph.clone().any(|index| search.is_match(lines[index]));
+        if matched == config.invert_match {
+            continue;
+        }
+
+        output.match_count += 1;
+        if !config.count_only {
+            if output.match_count > 1 {
+                output.lines.push(String::new());
+            }
+            for index in paragraph.clone() {
+                let at = LinePosition {
+                    index,
+                    offset: starts[index],
+                };
+                if search.is_match(lines[index]) {
+                    push_match(
+                        search,
+                        filename,
+                        &prefix,
+                        at,
+                        lines[index],
+                        None,
+                        &mut output,
+                    );
+                } else {
+                    push_plain(config, &prefix, at, lines[index], &mut output);
+                }
+            }
+        }
+        if output.match_count == limit {
+            output.lines_scanned = paragraph.end;
+            break;
+        }
+    }
+
+    if config.count_only {
+        output
+            .lines
+            .push(format!("{}{}", prefix, output.match_count));
+    }
+
+    output
+}
+
+/// The ranges of line indices of every paragraph: a run of lines that
+/// aren't blank, between blank lines or the ends of the file.
+fn paragraphs(lines: &[&str]) -> Vec<Range<usize>> {
+    let mut paragraphs = Vec::new();
+    let mut start = None;
+    for (index, line) in lines.iter().enumerate() {
+        match (line.trim().is_empty(), start) {
+            (false, None) => start = Some(index),
+            (true, Some(first)) => {
+                paragraphs.push(first..index);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(first) = start {
+        paragraphs.push(first..lines.len());
+    }
+
+    paragraphs
+}
+
+/// Formats a line that's printed as it is, like a context line, with its
+/// position and truncated if it's too long. Like in GNU grep, the file
+/// name and position are followed by `-` instead of the `:` after those
+/// of matching lines, to tell the two apart.
+fn push_plain(
+    config: &Config,
+    prefix: &str,
+    at: LinePosition,
+    line: &str,
+    output: &mut FileOutput,
+) {
+    let prefix = match prefix.strip_suffix(':') {
+        Some(name) => Cow::Owned(format!("{}-", name)),
+        None => Cow::Borrowed(prefix),
+    };
+    let position = position_prefix_with(config, at, '-');
+    let (shown, marker) = truncate(config, line);
+    let shown = tabs_expanded(config, format!("{}{}", shown, marker));
+    output
+        .lines
+        .push(format!("{}{}{}", prefix, position, shown));
+}
+
+/// Searches a file one line at a time, without holding more than the
+/// current line in memory. The output lines gathered so far are handed to
+/// `emit` after every match, which may take them, and reading stops when
+/// it returns false.
+fn search_stream<R, F>(
+    search: &Search,
+    filename: &str,
+    reader: R,
+    lossy: bool,
+    mut emit: F,
+) -> io::Result<FileOutput>
+where
+    R: BufRead,
+    F: FnMut(&mut Vec<String>) -> bool,
+{
+    let config = search.config;
+    let prefix = file_prefix(search, filename);
+    let mut output = FileOutput::default();
+    let mut count = 0;
+    let mut span = None;
+    let (first, last) = line_indices(config);
+    let mut seen = HashSet::new();
+    // The nearest heading above the current line, the current line when
+    // it's a heading itself, and the index of the last heading shown.
+    let mut heading: Option<(usize, String)> = None;
+    let mut next_heading = None;
+    let mut shown_heading = None;
+
+    // Reading stops as soon as the last line of the range has been seen.
+    for_each_line(reader, terminator(config), lossy, |at, line| {
+        let stripped;
+        let line = if prints_stripped(config) && line.contains('\x1b') {
+            stripped = strip_ansi(line);
+            stripped.as_str()
+        } else {
+            line
+        };
+        if let Some(next) = next_heading.take() {
+            heading = Some(next);
+        }
+        if search.is_heading(line) {
+            next_heading = Some((at.index, line.to_string()));
+        }
+        if at.index < first {
+            return true;
+        }
+        if search.timed_out_at(at.index) {
+            return false;
+        }
+        output.lines_scanned += 1;
+        if !is_listed(config, at.index)
+            || !search.selects(line)
+            || (config.unique && !seen.insert(search.unique_text(line).to_string()))
+        {
+            return at.index < last;
+        }
+
+        count += 1;
+        span = Some((span.map_or(at.index, |(first, _)| first), at.index));
+        if !config.count_only && !config.span {
+            if let Some((index, text)) = &heading {
+                if shown_heading != Some(*index) {
+                    output.lines.push(heading_line(&prefix, *index, text));
+                    shown_heading = Some(*index);
+                }
+            }
+            push_match(search, filename, &prefix, at, line, None, &mut output);
+            if !emit(&mut output.lines) {
+                return false;
+            }
+        }
+        count < config.max_count.unwrap_or(usize::MAX) && at.index < last
+    })?;
+
+    if config.count_only {
+        output.lines.push(format!("{}{}", prefix, count));
+    }
+    if config.span {
+        output.lines.push(span_line(&prefix, span, count));
+    }
+    output.match_count = count;
+
+    Ok(output)
+}
+
+/// Whether lines are printed with their escape sequences stripped, so
+/// they can be stripped as they're read. Byte offsets are then of the
+/// stripped lines too.
+fn prints_stripped(config: &Config) -> bool {
+    config.strip_ansi && !config.print_original
+}
+
+/// Removes the ANSI escape sequences from the text: control sequences
+/// like the `\x1b[01;31m` color codes, operating system commands like
+/// terminal titles and hyperlinks, and two-character escapes. An escape
+/// sequence cut off by the end of the text or of a line is removed up to
+/// there.
+///
+/// ## Example
+/// ```
+/// assert_eq!("error: disk full", minigrep::strip_ansi("\x1b[1;31merror\x1b[0m: disk full"));
+/// ```
+pub fn strip_ansi(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    for_each_visible_char(line, |_, c| stripped.push(c));
+    stripped
+}
+
+/// The text with its escape sequences removed, like `strip_ansi`, and the
+/// offset in the text of every byte that's left, followed by the text's
+/// length, so ranges of the stripped text can be mapped back onto it.
+fn strip_ansi_mapped(line: &str) -> (String, Vec<usize>) {
+    let mut stripped = String::with_capacity(line.len());
+    let mut offsets = Vec::with_capacity(line.len() + 1);
+    for_each_visible_char(line, |offset, c| {
+        stripped.push(c);
+        offsets.extend(offset..offset + c.len_utf8());
+    });
+    offsets.push(line.len());
+    (stripped, offsets)
+}
+
+/// Calls `visible` with the offset of every character of the text that
+/// isn't part of an ANSI escape sequence, and the character.
+fn for_each_visible_char<F: FnMut(usize, char)>(line: &str, mut visible: F) {
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        if c != '\x1b' {
+            visible(offset, c);
+            continue;
+        }
+        // An escape sequence cut off by the end of a line ends there, so
+        // it can't hide the lines after it.
+        match chars.next_if(|&(_, c)| c != '\n') {
+            // A control sequence ends at its first byte from `@` to `~`.
+            Some((_, '[')) => {
+                while let Some((_, c)) = chars.next_if(|&(_, c)| c != '\n') {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // An operating system command ends at a BEL or an `ESC \`.
+            Some((_, ']')) => {
+                while let Some((_, c)) = chars.next_if(|&(_, c)| c != '\n') {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if(|&(_, c)| c == '\\').is_some())
+                    {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The line printed for the heading of a section, from its 0-based index.
+fn heading_line(prefix: &str, index: usize, heading: &str) -> String {
+    format!("{}{}={}", prefix, index + 1, heading)
+}
+
+/// The line printed in span mode, from the 0-based indices of the first
+/// and last matching lines, if any line matched, and how many did.
+fn span_line(prefix: &str, span: Option<(usize, usize)>, count: usize) -> String {
+    match span {
+        Some((first, last)) => format!(
+            "{}first={} last={} count={}",
+            prefix,
+            first + 1,
+            last + 1,
+            count
+        ),
+        None => format!("{}no matches", prefix),
+    }
+}
+
+/// What every printed line of a file starts with.
+fn file_prefix(search: &Search, filename: &str) -> String {
+    if search.show_filenames {
+        let separator = if search.config.null { '\0' } else { ':' };
+        format!("{}{}", search.display_name(filename), separator)
+    } else {
+        String::new()
+    }
+}
+
+/// Where a line, or a match within it, starts in its file.
+#[derive(Clone, Copy)]
+struct LinePosition {
+    /// The 0-based index of the line.
+    index: usize,
+    /// The byte offset from the start of the file.
+    offset: usize,
+}
+
+/// The line number and byte offset columns of a matching line, for the
+/// options that ask for them.
+fn position_prefix(config: &Config, at: LinePosition) -> String {
+    position_prefix_with(config, at, ':')
+}
+
+/// The line number and byte offset columns, each followed by the
+/// separator.
+fn position_prefix_with(config: &Config, at: LinePosition, separator: char) -> String {
+    let mut position = String::new();
+    if config.show_line_numbers {
+        position.push_str(&format!("{}{}", at.index + 1, separator));
+    }
+    if config.byte_offset {
+        position.push_str(&format!("{}{}", at.offset, separator));
+    }
+    position
+}
+
+/// Records a single piece of matched text in only-matching mode.
+fn push_only_matching(
+    search: &Search,
+    prefix: &str,
+    at: LinePosition,
+    text: &str,
+    output: &mut FileOutput,
+) {
+    let line_number = position_prefix(search.config, at);
+    let text = if search.colored {
+        let mut colored = String::new();
+        push_colored(&mut colored, text, &search.config.match_color);
+        colored
+    } else {
+        text.to_string()
+    };
+
+    output.lines.push(format!(
+        "{}{}{}",
+        prefix,
+        line_number,
+        shell_escaped(search.config, tabs_expanded(search.config, text))
+    ));
+}
+
+/// The text of a match as it's printed, quoted in shell-escape mode.
+fn shell_escaped(config: &Config, text: String) -> String {
+    if config.shell_escape {
+        shell_quote(&text)
+    } else {
+        text
+    }
+}
+
+/// The text as it's printed, with its tabs expanded when that's asked for.
+fn tabs_expanded(config: &Config, text: String) -> String {
+    match config.tabs {
+        Some(width) if text.contains('\t') => expand_tabs(&text, width),
+        _ => text,
+    }
+}
+
+/// Formats a matching line, given its position, onto the output
+/// according to the output mode. The parts highlighted in color are the
+/// `ranges` of the line when they're given, or the matches found in it.
+fn push_match(
+    search: &Search,
+    filename: &str,
+    prefix: &str,
+    at: LinePosition,
+    line: &str,
+    ranges: Option<&[Range<usize>]>,
+    output: &mut FileOutput,
+) {
+    let config = search.config;
+    let mut line_number = position_prefix(config, at);
+    if config.count_per_line {
+        // The line number is always shown, with the count after it.
+        if !config.show_line_numbers {
+            line_number.insert_str(0, &format!("{}:", at.index + 1));
+        }
+        line_number.push_str(&format!("{}:", search.find_ranges(line).len()));
+    }
+    let replaced = search.replace_line(line);
+
+    if config.format == OutputFormat::Json {
+        output.json.push(serde_json::json!(JsonMatch {
+            file: search.display_name(filename),
+            line_number: if config.show_line_numbers {
+                Some(at.index + 1)
+            } else {
+                None
+            },
+            byte_offset: if config.byte_offset {
+                Some(at.offset)
+            } else {
+                None
+            },
+            text: &replaced,
+        }));
+    } else if config.vimgrep {
+        // Columns count bytes from 1, like the ones editors jump to.
+        let mut columns: Vec<usize> = search
+            .find_ranges(line)
+            .into_iter()
+            .map(|range| range.start + 1)
+            .collect();
+        if columns.is_empty() {
+            columns.push(1);
+        }
+        for column in columns {
+            output.lines.push(format!(
+                "{}:{}:{}:{}",
+                search.display_name(filename),
+                at.index + 1,
+                column,
+                line
+            ));
+        }
+    } else if config.only_matching {
+        for range in search.find_ranges(line) {
+            let at = LinePosition {
+                offset: at.offset + range.start,
+                ..at
+            };
+            push_only_matching(search, prefix, at, &line[range], output);
+        }
+    } else if search.colored && !config.invert_match && config.replace.is_none() {
+        let (shown, marker) = truncate(config, line);
+        let ranges: Vec<Range<usize>> = ranges
+            .map_or_else(|| search.find_ranges(line), <[_]>::to_vec)
+            .into_iter()
+            .filter(|range| range.start < shown.len())
+            .map(|range| range.start..range.end.min(shown.len()))
+            .collect();
+        let shown = highlight(shown, &ranges, &config.match_color);
+        let shown = tabs_expanded(config, format!("{}{}", shown, marker));
+        let shown = shell_escaped(config, shown);
+        output
+            .lines
+            .push(format!("{}{}{}", prefix, line_number, shown));
+    } else {
+        let (shown, marker) = truncate(config, &replaced);
+        let shown = tabs_expanded(config, format!("{}{}", shown, marker));
+        let shown = shell_escaped(config, shown);
+        output
+            .lines
+            .push(format!("{}{}{}", prefix, line_number, shown));
+    }
+}
+
+/// Quotes the text so a POSIX shell reads it back as one word, exactly as
+/// it is. It's wrapped in single quotes, inside which only a single quote
+/// needs escaping, by ending the quotes around an escaped one.
+///
+/// ## Example
+/// ```
+/// assert_eq!("'it'\\''s here'", minigrep::shell_quote("it's here"));
+/// ```
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Replaces every tab in the line with the spaces up to the next tab
+/// stop, where the stops are `width` columns apart, counting from the
+/// start of the line. Every other character takes one column, except the
+/// ANSI color codes of highlighted matches, which take none. A width of 0
+/// just removes the tabs.
+///
+/// ## Example
+/// ```
+/// assert_eq!("a   b       c", minigrep::expand_tabs("a\tb\t\tc", 4));
+/// ```
+pub fn expand_tabs(line: &str, width: usize) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\t' if width > 0 => {
+                let spaces = width - column % width;
+                expanded.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            '\t' => {}
+            '\x1b' => {
+                // A color code runs up to its final `m`.
+                expanded.push(c);
+                for c in chars.by_ref() {
+                    expanded.push(c);
+                    if c == 'm' {
+                        break;
+                    }
+                }
+            }
+            _ => {
+                expanded.push(c);
+                column += 1;
+            }
+        }
+    }
+
+    expanded
+}
+
+/// Splits a printed line into the part that fits in `max_line_length`
+/// characters and the marker to put after it, which is empty when the
+/// whole line fits.
+fn truncate<'a>(config: &Config, line: &'a str) -> (&'a str, &'static str) {
+    match config
+        .max_line_length
+        .and_then(|max| line.char_indices().nth(max))
+    {
+        Some((cut, _)) => (&line[..cut], TRUNCATION_MARKER),
+        None => (line, ""),
+    }
+}
+
+/// Splits contents into lines, without their `\n` or `\r\n` terminators.
+///
+/// Unlike `str::lines`, a carriage return is stripped from the last line
+/// even when no line feed follows it, so CRLF files always match and
+/// print their lines exactly like their LF equivalents.
+fn lines(contents: &str) -> impl Iterator<Item = &str> {
+    records(contents, b'\n')
+}
+
+/// Splits contents into lines like `lines` does, but on the given
+/// terminator. Carriage returns are only special before a `\n`. A last
+/// line without a terminator is a line like any other, and a terminator
+/// at the very end doesn't start an empty one.
+fn records(contents: &str, terminator: u8) -> impl Iterator<Item = &str> {
+    contents
+        .split_terminator(terminator as char)
+        .map(move |line| match terminator {
+            b'\n' => line.strip_suffix('\r').unwrap_or(line),
+            _ => line,
+        })
+}
+
+/// What ends each line of the input and the output: the line delimiter
+/// when there is one, or a NUL byte in null-data mode, for input like the
+/// output of `find -print0`.
+fn terminator(config: &Config) -> u8 {
+    if let Some(delimiter) = config.line_delimiter {
+        delimiter as u8
+    } else if config.null_data {
+        b'\0'
+    } else {
+        b'\n'
+    }
+}
+
+/// Calls `f` with the position and text of each line read from the
+/// reader, without its terminator, until `f` returns
+/// `false` or the reader runs out. Lines that aren't valid UTF-8 are
+/// decoded lossily when `lossy` is set, and are an `InvalidData` error
+/// otherwise. Lines end in `terminator`, or `\r\n` when that's `\n`.
+fn for_each_line<R, F>(mut reader: R, terminator: u8, lossy: bool, mut f: F) -> io::Result<()>
+where
+    R: BufRead,
+    F: FnMut(LinePosition, &str) -> bool,
+{
+    let mut line = Vec::new();
+    let mut at = LinePosition {
+        index: 0,
+        offset: 0,
+    };
+
+    while reader.read_until(terminator, &mut line)? > 0 {
+        let text = decode(&line, lossy)?;
+        let text = match text.strip_suffix(terminator as char) {
+            Some(text) if terminator == b'\n' => text.strip_suffix('\r').unwrap_or(text),
+            Some(text) => text,
+            None => text
+                .strip_suffix('\r')
+                .filter(|_| terminator == b'\n')
+                .unwrap_or(&text),
+        };
+        if !f(at, text) {
+            break;
+        }
+
+        // The terminator is counted as it was read, like `\r\n` or `\n`.
+        at.index += 1;
+        at.offset += line.len();
+        line.clear();
+    }
+
+    Ok(())
+}
+
+/// Reads lines from the reader one at a time and calls `on_match` with
+/// each one that contains the query, so arbitrarily large inputs can be
+/// searched without holding them in memory.
+///
+/// ## Errors
+/// Returns any error from the reader, including `InvalidData` for input
+/// that isn't valid UTF-8.
+///
+/// ## Example
+/// ```
+/// let reader = std::io::Cursor::new("To here\nbut not there.\nhere to there.\n");
+/// let mut matches = Vec::new();
+///
+/// minigrep::for_each_match(reader, "to", |line| matches.push(line.to_string())).unwrap();
+///
+/// assert_eq!(vec!["here to there."], matches);
+/// ```
+pub fn for_each_match<R, F>(reader: R, query: &str, mut on_match: F) -> io::Result<()>
+where
+    R: BufRead,
+    F: FnMut(&str),
+{
+    for_each_line(reader, b'\n', false, |_, line| {
+        if line.contains(query) {
+            on_match(line);
+        }
+        true
+    })
+}
+
+/// Reads lines from the reader one at a time like `for_each_match`, but
+/// with a choice of case sensitivity, calling `on_match` with the number
+/// of each matching line, counting from 1, and the line itself. Returns
+/// how many lines matched.
+///
+/// ## Errors
+/// Returns any error from the reader, including `InvalidData` for input
+/// that isn't valid UTF-8.
+///
+/// ## Example
+/// ```
+/// let reader = std::io::Cursor::new("To here\nbut not there.\nhere to there.\n");
+/// let mut matches = Vec::new();
+///
+/// let count = minigrep::search_reader(reader, "to", false, |number, line| {
+///     matches.push(format!("{}:{}", number, line))
+/// })
+/// .unwrap();
+///
+/// assert_eq!(2, count);
+/// assert_eq!(vec!["1:To here", "3:here to there."], matches);
+/// ```
+pub fn search_reader<R: BufRead>(
+    reader: R,
+    query: &str,
+    case_sensitive: bool,
+    mut on_match: impl FnMut(usize, &str),
+) -> io::Result<usize> {
+    let query = if case_sensitive {
+        query.to_string()
+    } else {
+        query.to_lowercase()
+    };
+    let mut count = 0;
+    for_each_line(reader, b'\n', false, |at, line| {
+        let matched = if case_sensitive {
+            line.contains(&query)
+        } else {
+            line.to_lowercase().contains(&query)
+        };
+        if matched {
+            count += 1;
+            on_match(at.index + 1, line);
+        }
+        true
+    })?;
+
+    Ok(count)
+}
+
+/// A file to be searched.
+struct Input {
+    name: String,
+    /// For a file found by walking a directory, rather than named
+    /// directly, its path below that directory.
+    walked: Option<String>,
+}
+
+/// Lists the files named in the config, expanding glob patterns and
+/// replacing each directory with the files underneath it in recursive
+/// mode. Files are named by their absolute paths in absolute-path mode.
+/// Patterns that match nothing and directories that can't be walked are
+/// reported on stderr, with the first error kept in `failed`. Entries
+/// the walk can't read, because permission is denied or they're gone, are
+/// only warned about, and counted in `skipped`.
+///
+/// ## Errors
+/// Fails with `InvalidConfig` when an include or exclude glob is invalid.
+fn expand_inputs(
+    config: &Config,
+    failed: &mut Option<MinigrepError>,
+    skipped: &mut usize,
+) -> Result<Vec<Input>, MinigrepError> {
+    let options = walk::WalkOptions {
+        max_depth: config.max_depth,
+        include: parse_globs(&config.include)?,
+        exclude: parse_globs(&config.exclude)?,
+        hidden: config.hidden,
+        follow: config.follow,
+    };
+    let mut inputs = Vec::new();
+    let mut filenames = Vec::new();
+
+    for filename in &config.filenames {
+        match expand_glob(filename) {
+            Ok(expanded) => filenames.extend(expanded),
+            Err(e) => {
+                eprintln!("{}", e);
+                failed.get_or_insert(e);
+            }
+        }
+    }
+
+    for filename in &filenames {
+        let path = Path::new(filename);
+        if !config.recursive || filename == STDIN_FILENAME || !path.is_dir() {
+            inputs.push(Input {
+                name: filename.clone(),
+                walked: None,
+            });
+            continue;
+        }
+
+        let mut files = Vec::new();
+        let mut errors = Vec::new();
+        walk::walk(path, &options, &mut files, &mut errors);
+
+        for (path, e) in errors {
+            if is_unreadable(&e) {
+                eprintln!(
+                    "{}: skipping file that can't be read: {}",
+                    path.display(),
+                    e
+                );
+                *skipped += 1;
+                continue;
+            }
+            eprintln!("{}: {}", path.display(), e);
+            failed.get_or_insert(MinigrepError::for_file(&path.to_string_lossy(), e));
+        }
+
+        inputs.extend(files.into_iter().map(|file| {
+            Input {
+                walked: Some(
+                    file.strip_prefix(path)
+                        .unwrap_or(&file)
+                        .to_string_lossy()
+                        .into_owned(),
+                ),
+                name: file.to_string_lossy().into_owned(),
+            }
+        }));
+    }
+
+    if config.absolute_path {
+        for input in &mut inputs {
+            input.name = absolute_name(&input.name);
+        }
+    }
+
+    Ok(inputs)
+}
+
+/// The canonical absolute path of the file, or its name as it is, with a
+/// warning on stderr, when that can't be found, like for a broken
+/// symlink. Standard input keeps its name.
+fn absolute_name(filename: &str) -> String {
+    if filename == STDIN_FILENAME {
+        return filename.to_string();
+    }
+    match fs::canonicalize(filename) {
+        Ok(path) => path.to_string_lossy().into_owned(),
+        Err(e) => {
+            eprintln!("{}: can't find the absolute path: {}", filename, e);
+            filename.to_string()
+        }
+    }
+}
+
+/// The names the inputs are shown under when they're printed relative to
+/// a base directory, by the names they're opened by. A base that can't be
+/// found is reported on stderr, with the error kept in `failed`, and the
+/// names are left as they are.
+fn relative_names(
+    config: &Config,
+    inputs: &[Input],
+    failed: &mut Option<MinigrepError>,
+) -> HashMap<String, String> {
+    let base = match config
+        .relative_to
+        .as_deref()
+        .map(|base| (base, fs::canonicalize(base)))
+    {
+        Some((_, Ok(base))) => base,
+        Some((base, Err(e))) => {
+            eprintln!("{}: {}", base, e);
+            failed.get_or_insert(MinigrepError::for_file(base, e));
+            return HashMap::new();
+        }
+        None => return HashMap::new(),
+    };
+
+    inputs
+        .iter()
+        .map(|input| (input.name.clone(), relative_name(&input.name, &base)))
+        .collect()
+}
+
+/// The path of the file relative to the canonical base directory, or its
+/// absolute path, with a note on stderr, when it has no common root with
+/// the base. Standard input keeps its name.
+fn relative_name(filename: &str, base: &Path) -> String {
+    if filename == STDIN_FILENAME {
+        return filename.to_string();
+    }
+    let absolute = absolute_name(filename);
+    match relative_path(Path::new(&absolute), base) {
+        Some(path) => path.to_string_lossy().into_owned(),
+        None => {
+            eprintln!("{}: can't be made relative to {}", absolute, base.display());
+            absolute
+        }
+    }
+}
+
+/// The path that leads from the base to the path, through their longest
+/// common ancestor, when they have one.
+fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        return None;
+    }
+
+    let mut relative: PathBuf = base[common..]
+        .iter()
+        .map(|_| Component::ParentDir)
+        .collect();
+    relative.extend(&path[common..]);
+    if relative.as_os_str().is_empty() {
+        relative.push(Component::CurDir);
+    }
+    Some(relative)
+}
+
+/// Parses the include or exclude globs.
+///
+/// ## Errors
+/// Fails with `InvalidConfig` naming the first glob that's invalid.
+pub(crate) fn parse_globs(globs: &[String]) -> Result<Vec<glob::Pattern>, MinigrepError> {
+    globs
+        .iter()
+        .map(|glob| {
+            glob::Pattern::new(glob)
+                .map_err(|e| MinigrepError::InvalidConfig(format!("Invalid glob {}: {}", glob, e)))
+        })
+        .collect()
+}
+
+/// Reads the NUL-separated file names in a file, or in stdin when it's
+/// `STDIN_FILENAME`, like the output of `find -print0`. Empty names are
+/// skipped.
+///
+/// ## Errors
+/// Fails with `FileNotFound` or `Io` when the list can't be read.
+fn read_file_list(path: &str) -> Result<Vec<String>, MinigrepError> {
+    let list = if path == STDIN_FILENAME {
+        let mut list = Vec::new();
+        io::stdin()
+            .read_to_end(&mut list)
+            .map_err(|e| MinigrepError::for_file(path, e))?;
+        list
+    } else {
+        fs::read(path).map_err(|e| MinigrepError::for_file(path, e))?
+    };
+
+    Ok(list
+        .split(|&byte| byte == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect())
+}
+
+/// Expands a file name containing glob wildcards into the sorted paths it
+/// matches. Names without wildcards, and names of files that really exist
+/// despite containing them, are passed through unchanged.
+fn expand_glob(filename: &str) -> Result<Vec<String>, MinigrepError> {
+    if !filename.contains(&['*', '?', '['][..]) || Path::new(filename).exists() {
+        return Ok(vec![filename.to_string()]);
+    }
+
+    let paths = glob::glob(filename).map_err(|e| {
+        MinigrepError::InvalidConfig(format!("Invalid pattern {}: {}", filename, e))
+    })?;
+    let expanded: Vec<String> = paths
+        .filter_map(Result::ok)
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+
+    if expanded.is_empty() {
+        return Err(MinigrepError::UnmatchedPattern(filename.to_string()));
+    }
+
+    Ok(expanded)
+}
+
+/// Opens a file for reading, or stdin when the file name is
+/// `STDIN_FILENAME`, decompressing it if it's gzipped and decoding it when
+/// it has an encoding.
+fn open_input(config: &Config, filename: &str) -> io::Result<Box<dyn Read>> {
+    let input: Box<dyn Read> = if filename == STDIN_FILENAME {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(fs::File::open(filename)?)
+    };
+
+    let input: Box<dyn Read> = if is_compressed(config, filename) {
+        Box::new(Gunzip(flate2::read::MultiGzDecoder::new(input)))
+    } else {
+        input
+    };
+
+    match config.encoding.as_deref() {
+        Some(label) => decode_input(input, label),
+        None => Ok(input),
+    }
+}
+
+/// Reads everything from the input and decodes it from the encoding with
+/// the label, handing back the text as UTF-8. Invalid sequences become
+/// U+FFFD, and a byte order mark overrides the encoding.
+fn decode_input(mut input: Box<dyn Read>, label: &str) -> io::Result<Box<dyn Read>> {
+    let encoding = encoding_rs::Encoding::for_label(label.as_bytes()).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unknown encoding {}", label),
+        )
+    })?;
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+    let (text, _, _) = encoding.decode(&bytes);
+
+    Ok(Box::new(io::Cursor::new(text.into_owned().into_bytes())))
+}
+
+/// Whether the file is read through a gzip decoder.
+fn is_compressed(config: &Config, filename: &str) -> bool {
+    config.decompress || filename.ends_with(".gz")
+}
+
+/// Reads a gzip stream, turning what the decoder reports about corrupt
+/// data into errors that say so, rather than ones that look like the
+/// decompressed text isn't valid UTF-8.
+struct Gunzip<R>(flate2::read::MultiGzDecoder<R>);
+
+impl<R: Read> Read for Gunzip<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::UnexpectedEof => {
+                io::Error::other(format!("not a valid gzip file: {}", e))
+            }
+            _ => e,
+        })
+    }
+}
+
+/// Rewrites the file with every match on its matching lines replaced,
+/// leaving their line terminators alone, and prints nothing.
+fn replace_in_place(search: &Search, filename: &str, lossy: bool) -> io::Result<FileOutput> {
+    if is_compressed(search.config, filename) {
+        return Err(io::Error::other("can't rewrite a compressed file in place"));
+    }
+    let contents = read_contents(open_input(search.config, filename)?, lossy)?;
+    let lines: Vec<&str> = records(&contents, terminator(search.config)).collect();
+    let starts = line_starts(&contents, terminator(search.config));
+    let matched = matching_lines(search, &contents, &lines);
+
+    let mut replaced = String::with_capacity(contents.len());
+    let mut last = 0;
+    for &index in &matched {
+        replaced.push_str(&contents[last..starts[index]]);
+        replaced.push_str(&search.replace_line(lines[index]));
+        last = starts[index] + lines[index].len();
+    }
+    replaced.push_str(&contents[last..]);
+
+    // The lines after a timeout weren't looked at, so nothing is written.
+    if search.timed_out() {
+        return Err(timed_out_error());
+    }
+    if replaced != contents {
+        write_replacing(Path::new(filename), &replaced)?;
+    }
+
+    Ok(FileOutput {
+        match_count: matched.len(),
+        lines_scanned: lines_scanned(search.config, lines.len(), &matched),
+        ..FileOutput::default()
+    })
+}
+
+/// Writes the new contents to a temporary file beside the file and then
+/// renames it over the file, so the file is never left half written.
+fn write_replacing(path: &Path, contents: &str) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    let temp = path.with_file_name(format!(".{}.minigrep-tmp", name));
+
+    let result = fs::write(&temp, contents)
+        .and_then(|_| fs::set_permissions(&temp, fs::metadata(path)?.permissions()))
+        .and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+
+    result
+}
+
+/// Searches a file mapped into memory, rather than copied into a buffer.
+///
+/// The file is expected to stay the same while it's searched. If another
+/// process truncates it, reading past the new end may crash, and if its
+/// length changed at all by the end of the search, the search fails
+/// instead of returning output that's out of date.
+fn search_mapped(search: &Search, filename: &str, lossy: bool) -> io::Result<FileOutput> {
+    let file = fs::File::open(filename)?;
+    let len = file.metadata()?.len();
+
+    // SAFETY: nothing stops another process from changing the file while
+    // it's mapped, which `mmap` makes the caller promise not to do by
+    // truncating it. The length is only compared after the search, to
+    // reject output that may be out of date, and the map is dropped
+    // before returning.
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    let output = search_contents(search, filename, &decode(&map, lossy)?);
+
+    if file.metadata()?.len() != len {
+        return Err(io::Error::other("file changed while it was being searched"));
+    }
+
+    Ok(output)
+}
+
+/// Reads everything from the reader, decoding it like `for_each_line`.
+fn read_contents<R: Read>(mut reader: R, lossy: bool) -> io::Result<String> {
+    let mut contents = Vec::new();
+    reader.read_to_end(&mut contents)?;
+
+    Ok(decode(&contents, lossy)?.into_owned())
+}
+
+fn decode(bytes: &[u8], lossy: bool) -> io::Result<Cow<'_, str>> {
+    if lossy {
+        Ok(String::from_utf8_lossy(bytes))
+    } else {
+        std::str::from_utf8(bytes)
+            .map(Cow::Borrowed)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// The name a file is shown under in the output.
+fn display_name(filename: &str) -> &str {
+    if filename == STDIN_FILENAME {
+        "(standard input)"
+    } else {
+        filename
+    }
+}
+
+/// A line picked out by a search, with where it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// The name of the file the line is from, when it's known.
+    pub file: Option<String>,
+    /// The number of the line, counting from 1.
+    pub line_number: usize,
+    /// The line, without its terminator.
+    pub text: String,
+    /// Where the line starts, in bytes from the start of the contents.
+    pub byte_offset: usize,
+}
+
+/// An iterator over the lines of some contents that a search with a
+/// config picks out, in order, as `Match`es. The lines are the ones
+/// `search_with_config` returns, so the same options are minded and
+/// ignored.
+///
+/// ## Example
+/// ```
+/// let config = minigrep::ConfigBuilder::new().query("to").build().unwrap();
+/// let contents = "up to you.\nbut not there.\nhere to there.";
+/// let found: Vec<minigrep::Match> = minigrep::Matches::new(&config, contents)
+///     .with_file("poem.txt")
+///     .collect();
+///
+/// assert_eq!(2, found.len());
+/// assert_eq!(Some("poem.txt".to_string()), found[1].file);
+/// assert_eq!(3, found[1].line_number);
+/// assert_eq!("here to there.", found[1].text);
+/// assert_eq!(26, found[1].byte_offset);
+/// ```
+pub struct Matches<'a> {
+    file: Option<String>,
+    lines: Vec<&'a str>,
+    starts: Vec<usize>,
+    matched: std::vec::IntoIter<usize>,
+}
+
+impl<'a> Matches<'a> {
+    /// Searches the contents with the config, finding every match up
+    /// front so the iterator can hand them out.
+    pub fn new(config: &Config, contents: &'a str) -> Matches<'a> {
+        let search = Search::lenient(config);
+        let lines: Vec<&str> = records(contents, terminator(config)).collect();
+        let matched = matching_lines(&search, contents, &lines);
+
+        Matches {
+            file: None,
+            lines,
+            starts: line_starts(contents, terminator(config)),
+            matched: matched.into_iter(),
+        }
+    }
+
+    /// Names the file the contents are from in every match.
+    pub fn with_file<S: Into<String>>(mut self, file: S) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+}
+
+impl Iterator for Matches<'_> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        let index = self.matched.next()?;
+        Some(Match {
+            file: self.file.clone(),
+            line_number: index + 1,
+            text: self.lines[index].to_string(),
+            byte_offset: self.starts[index],
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.matched.size_hint()
+    }
+}
+
+/// Searches contents already in memory for the lines that `run` would
+/// pick out with the config, minding its matching options like case,
+/// invert, word, regex, field, line range and maximum count. A query
+/// that isn't a valid regular expression in regex mode is matched as
+/// plain text instead.
+///
+/// Options about finding and reading files, like `filenames`,
+/// `recursive`, `decompress` or `timeout`, and about how lines are
+/// printed, like `color` or `show_line_numbers`, are ignored.
+///
+/// ## Example
+/// ```
+/// let config = minigrep::ConfigBuilder::new()
+///     .query("to")
+///     .case_sensitive(false)
+///     .word_match(true)
+///     .build()
+///     .unwrap();
+/// let contents = "To here\nbut not there.\nhere to there.\ntomorrow";
+///
+/// assert_eq!(
+///     vec!["To here", "here to there."],
+///     minigrep::search_with_config(&config, contents)
+/// );
+/// ```
+pub fn search_with_config<'a>(config: &Config, contents: &'a str) -> Vec<&'a str> {
+    let search = Search::lenient(config);
+    let lines: Vec<&str> = records(contents, terminator(config)).collect();
+
+    matching_lines(&search, contents, &lines)
+        .into_iter()
+        .map(|index| lines[index])
+        .collect()
+}
+
+/// Searches for the query in contents with case sensitivity.
+///
+/// ## Example
+/// ```
+/// let query = "to";
+/// let contents = "\
+///     To here\n\
+///     but not there.\n\
+///     here to there.";
+///
+/// assert_eq!(vec!["here to there."], minigrep::search(query, &contents));
+/// ```
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    lines_containing(query, contents).collect()
+}
+
+/// Lazily searches for the query in contents with case sensitivity, so
+/// callers that only want the first few matches don't pay for the rest.
+///
+/// ## Example
+/// ```
+/// let query = "to";
+/// let contents = "\
+///     up to you.\n\
+///     but not there.\n\
+///     here to there.";
+///
+/// assert_eq!(Some("up to you."), minigrep::search_iter(query, &contents).next());
+/// ```
+pub fn search_iter<'a>(query: &'a str, contents: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    lines_containing(query, contents)
+}
+
+/// Like `search_iter`, but the query may be borrowed for less time than
+/// the contents.
+fn lines_containing<'a, 'q>(query: &'q str, contents: &'a str) -> impl Iterator<Item = &'a str> + 'q
+where
+    'a: 'q,
+{
+    lines(contents).filter(move |line| line.contains(query))
+}
+
+/// Searches for the query in contents like `search`, but as raw bytes,
+/// so neither needs to be valid UTF-8. Lines are split on `\n` bytes,
+/// without the `\r` of a `\r\n` ending, and nothing after a final line
+/// break counts as a line.
+///
+/// ## Example
+/// ```
+/// let contents = b"caf\xe9 rust\n\xff\xfe\nrusty\n";
+///
+/// assert_eq!(
+///     vec![&b"caf\xe9 rust"[..], &b"rusty"[..]],
+///     minigrep::search_bytes(b"rust", contents)
+/// );
+/// ```
+pub fn search_bytes<'a>(query: &[u8], contents: &'a [u8]) -> Vec<&'a [u8]> {
+    let finder = memchr::memmem::Finder::new(query);
+    let contents = contents.strip_suffix(b"\n").unwrap_or(contents);
+    if contents.is_empty() {
+        return Vec::new();
+    }
+
+    contents
+        .split(|&byte| byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| finder.find(line).is_some())
+        .collect()
+}
+
+/// Searches for the query in contents with case insensitivity.
+///
+/// ## Example
+/// ```
+/// let query = "tO";
+/// let contents = "\
+///     To here\n\
+///     but not there.\n\
+///     here to there.";
+///
+/// assert_eq!(vec!["To here", "here to there."],
+///     minigrep::search_case_insensitive(query, &contents));
+/// ```
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    search_case_insensitive_iter(query, contents).collect()
+}
+
+/// Lazily searches for the query in contents with case insensitivity.
+///
+/// ## Example
+/// ```
+/// let query = "tO";
+/// let contents = "\
+///     To here\n\
+///     but not there.\n\
+///     here to there.";
+///
+/// assert_eq!(Some("here to there."),
+///     minigrep::search_case_insensitive_iter(query, &contents).nth(1));
+/// ```
+pub fn search_case_insensitive_iter<'a>(
+    query: &str,
+    contents: &'a str,
+) -> impl Iterator<Item = &'a str> + 'a {
+    let query = query.to_lowercase();
+
+    lines(contents).filter(move |line| line.to_lowercase().contains(&query))
+}
+
+/// Searches for the query in contents without case sensitivity, comparing
+/// the full Unicode case folding of both rather than their lowercase, so
+/// characters that fold to several others also match those, like `ß` and
+/// `SS`.
+///
+/// ## Example
+/// ```
+/// let query = "STRASSE";
+/// let contents = "\
+///     Hauptstraße 1\n\
+///     Marktplatz 2";
+///
+/// assert_eq!(vec!["Hauptstraße 1"], minigrep::search_case_folded(query, &contents));
+/// assert!(minigrep::search_case_insensitive(query, &contents).is_empty());
+/// ```
+pub fn search_case_folded<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let query = caseless::default_case_fold_str(query);
+
+    lines(contents)
+        .filter(|line| caseless::default_case_fold_str(line).contains(&query))
+        .collect()
+}
+
+/// Searches for the query in contents with case sensitivity, stopping
+/// as soon as `limit` matching lines are found.
+///
+/// ## Example
+/// ```
+/// let query = "to";
+/// let contents = "\
+///     up to you.\n\
+///     but not there.\n\
+///     here to there.";
+///
+/// assert_eq!(vec!["up to you."], minigrep::search_limited(query, &contents, 1));
+/// ```
+pub fn search_limited<'a>(query: &str, contents: &'a str, limit: usize) -> Vec<&'a str> {
+    lines_containing(query, contents).take(limit).collect()
+}
+
+/// Searches for lines containing the query with at most `max_distance`
+/// typos, counted as the Levenshtein distance between the query and the
+/// part of the line closest to it.
+///
+/// ## Example
+/// ```
+/// let query = "colour";
+/// let contents = "\
+///     Pick a color.\n\
+///     Pick a number.";
+///
+/// assert_eq!(vec!["Pick a color."], minigrep::search_fuzzy(query, &contents, 1));
+/// assert!(minigrep::search_fuzzy(query, &contents, 0).is_empty());
+/// ```
+pub fn search_fuzzy<'a>(query: &str, contents: &'a str, max_distance: usize) -> Vec<&'a str> {
+    lines(contents)
+        .filter(|line| fuzzy_find(query, line, true).0 <= max_distance)
+        .collect()
+}
+
+/// Searches for lines containing any of the patterns, like `search` does
+/// for one of them.
+///
+/// ## Example
+/// ```
+/// let patterns = vec!["to".to_string(), "Not".to_string()];
+/// let contents = "\
+///     up to you.\n\
+///     but not there.\n\
+///     here or there.";
+///
+/// assert_eq!(vec!["up to you."], minigrep::search_any(&patterns, &contents, true));
+/// assert_eq!(vec!["up to you.", "but not there."],
+///     minigrep::search_any(&patterns, &contents, false));
+/// ```
+pub fn search_any<'a>(
+    patterns: &[String],
+    contents: &'a str,
+    case_sensitive: bool,
+) -> Vec<&'a str> {
+    let patterns: Vec<String> = if case_sensitive {
+        patterns.to_vec()
+    } else {
+        patterns
+            .iter()
+            .map(|pattern| pattern.to_lowercase())
+            .collect()
+    };
+
+    lines(contents)
+        .filter(|line| {
+            let line: Cow<str> = if case_sensitive {
+                Cow::Borrowed(line)
+            } else {
+                Cow::Owned(line.to_lowercase())
+            };
+            patterns
+                .iter()
+                .any(|pattern| line.contains(pattern.as_str()))
+        })
+        .collect()
+}
+
+/// Replaces every occurrence of the query in contents with the
+/// replacement, and returns the whole text with the replacements made.
+///
+/// ## Example
+/// ```
+/// let contents = "\
+///     Rust: safe, rust.\n\
+///     Pick three.";
+///
+/// assert_eq!("Iron: safe, rust.\nPick three.",
+///     minigrep::replace_matches("Rust", "Iron", &contents, true));
+/// assert_eq!("Iron: safe, Iron.\nPick three.",
+///     minigrep::replace_matches("Rust", "Iron", &contents, false));
+/// ```
+pub fn replace_matches(
+    query: &str,
+    replacement: &str,
+    contents: &str,
+    case_sensitive: bool,
+) -> String {
+    splice(
+        contents,
+        &literal_ranges(contents, query, case_sensitive),
+        replacement,
+    )
+    .into_owned()
+}
+
+/// Searches for lines that start with the query. Lines aren't trimmed
+/// first, so a line indented with whitespace only starts with a query
+/// that begins with the same whitespace.
+///
+/// ## Example
+/// ```
+/// let contents = "\
+///     Rust: safe.\n\
+///     Trust rust\n  \
+///     rust, indented";
+///
+/// assert_eq!(vec!["Rust: safe."], minigrep::search_starts_with("rust", &contents, false));
+/// assert!(minigrep::search_starts_with("rust", &contents, true).is_empty());
+/// ```
+pub fn search_starts_with<'a>(
+    query: &str,
+    contents: &'a str,
+    case_sensitive: bool,
+) -> Vec<&'a str> {
+    if case_sensitive {
+        return lines(contents)
+            .filter(|line| line.starts_with(query))
+            .collect();
+    }
+
+    let query = query.to_lowercase();
+    lines(contents)
+        .filter(|line| line.to_lowercase().starts_with(&query))
+        .collect()
+}
+
+/// Searches for lines that end with the query. Like `search_starts_with`,
+/// lines aren't trimmed first, so trailing whitespace has to be part of
+/// the query to match.
+///
+/// ## Example
+/// ```
+/// let contents = "\
+///     Rust: safe.\n\
+///     Trust rust\n\
+///     rust, Rust ";
+///
+/// assert_eq!(vec!["Trust rust"], minigrep::search_ends_with("rust", &contents, true));
+/// assert_eq!(vec!["rust, Rust "], minigrep::search_ends_with("rust ", &contents, false));
+/// ```
+pub fn search_ends_with<'a>(query: &str, contents: &'a str, case_sensitive: bool) -> Vec<&'a str> {
+    if case_sensitive {
+        return lines(contents)
+            .filter(|line| line.ends_with(query))
+            .collect();
+    }
+
+    let query = query.to_lowercase();
+    lines(contents)
+        .filter(|line| line.to_lowercase().ends_with(&query))
+        .collect()
+}
+
+/// Searches for the paragraphs that contain the query, where paragraphs
+/// are runs of lines separated by blank lines, which may hold whitespace.
+/// Each is returned whole, from the start of its first line to the end of
+/// its last, with the line breaks between them.
+///
+/// ## Example
+/// ```
+/// let contents = "\
+///     Rust:\n\
+///     safe, fast.\n\
+///     \n\
+///     Pick three:\n\
+///     productive, trusted.\n";
+///
+/// assert_eq!(
+///     vec!["Pick three:\nproductive, trusted."],
+///     minigrep::search_paragraphs("trust", &contents, true)
+/// );
+/// ```
+pub fn search_paragraphs<'a>(query: &str, contents: &'a str, case_sensitive: bool) -> Vec<&'a str> {
+    let lines: Vec<&str> = lines(contents).collect();
+    let starts = line_starts(contents, b'\n');
+    let query = if case_sensitive {
+        query.to_string()
+    } else {
+        query.to_lowercase()
+    };
+
+    paragraphs(&lines)
+        .into_iter()
+        .filter(|paragraph| {
+            lines[paragraph.clone()].iter().any(|line| {
+                if case_sensitive {
+                    line.contains(&query)
+                } else {
+                    line.to_lowercase().contains(&query)
+                }
+            })
+        })
+        .map(|paragraph| {
+            let last = paragraph.end - 1;
+            &contents[starts[paragraph.start]..starts[last] + lines[last].len()]
+        })
+        .collect()
+}
+
+/// Searches for the lines that contain the query but not the excluded
+/// text, both matched with the same case sensitivity.
+///
+/// ## Example
+/// ```
+/// let contents = "\
+///     error: disk full\n\
+///     Error: Timeout\n\
+///     warning: timeout";
+///
+/// assert_eq!(
+///     vec!["error: disk full"],
+///     minigrep::search_excluding("error", "timeout", &contents, false)
+/// );
+/// assert_eq!(
+///     vec!["error: disk full", "Error: Timeout"],
+///     minigrep::search_excluding("rror", "timeout", &contents, true)
+/// );
+/// ```
+pub fn search_excluding<'a>(
+    query: &str,
+    exclude: &str,
+    contents: &'a str,
+    case_sensitive: bool,
+) -> Vec<&'a str> {
+    if case_sensitive {
+        return lines(contents)
+            .filter(|line| line.contains(query) && !line.contains(exclude))
+            .collect();
+    }
+
+    let (query, exclude) = (query.to_lowercase(), exclude.to_lowercase());
+    lines(contents)
+        .filter(|line| {
+            let line = line.to_lowercase();
+            line.contains(&query) && !line.contains(&exclude)
+        })
+        .collect()
+}
+
+/// Searches for the query in contents with case sensitivity, only looking
+/// at the lines from `start` to `end`, inclusive and counting from 1, and
+/// pairs each matching line with its line number. Lines after `end`
+/// aren't looked at, and an `end` beyond the last line is fine.
+///
+/// ## Example
+/// ```
+/// let query = "to";
+/// let contents = "\
+///     up to you.\n\
+///     but not there.\n\
+///     here to there.";
+///
+/// assert_eq!(vec![(3, "here to there.")],
+///     minigrep::search_line_range(query, &contents, 2, 10));
+/// ```
+pub fn search_line_range<'a>(
+    query: &str,
+    contents: &'a str,
+    start: usize,
+    end: usize,
+) -> Vec<(usize, &'a str)> {
+    lines(contents)
+        .enumerate()
+        .take(end)
+        .skip(start.saturating_sub(1))
+        .filter(|(_, line)| line.contains(query))
+        .map(|(index, line)| (index + 1, line))
+        .collect()
+}
+
+/// Searches for lines whose `field`th field, counting from 1 and split on
+/// `delimiter`, contains the query. Lines with fewer fields don't match.
+///
+/// ## Example
+/// ```
+/// let contents = "\
+///     name,language\n\
+///     ferris,Rust\n\
+///     rusty,Go\n\
+///     ghost";
+///
+/// assert_eq!(vec!["ferris,Rust"],
+///     minigrep::search_field("rust", &contents, 2, ",", false));
+/// assert!(minigrep::search_field("rust", &contents, 2, ",", true).is_empty());
+/// ```
+pub fn search_field<'a>(
+    query: &str,
+    contents: &'a str,
+    field: usize,
+    delimiter: &str,
+    case_sensitive: bool,
+) -> Vec<&'a str> {
+    let query = if case_sensitive {
+        query.to_string()
+    } else {
+        query.to_lowercase()
+    };
+
+    lines(contents)
+        .filter(
+            |line| match line.split(delimiter).nth(field.wrapping_sub(1)) {
+                Some(text) if case_sensitive => text.contains(&query),
+                Some(text) => text.to_lowercase().contains(&query),
+                None => false,
+            },
+        )
+        .collect()
+}
+
+/// The smallest edit distance between the query and any part of the
+/// line, with the byte range of the first part that close.
+///
+/// This is Levenshtein distance where skipping the text before and after
+/// the part is free, computed one column of the table per line character.
+/// Each cell remembers where its part of the line started.
+fn fuzzy_find(query: &str, line: &str, case_sensitive: bool) -> (usize, Range<usize>) {
+    let same =
+        |a: char, b: char| a == b || (!case_sensitive && a.to_lowercase().eq(b.to_lowercase()));
+    let query: Vec<char> = query.chars().collect();
+
+    let mut column: Vec<(usize, usize)> = (0..=query.len()).map(|i| (i, 0)).collect();
+    let mut best = (query.len(), 0..0);
+
+    for (at, c) in line.char_indices() {
+        let end = at + c.len_utf8();
+        let mut next = Vec::with_capacity(column.len());
+        next.push((0, end));
+
+        for (i, &q) in query.iter().enumerate() {
+            let (cost, start) = column[i];
+            let mut cell = (cost + !same(q, c) as usize, start);
+            if column[i + 1].0 + 1 < cell.0 {
+                cell = (column[i + 1].0 + 1, column[i + 1].1);
+            }
+            if next[i].0 + 1 < cell.0 {
+                cell = (next[i].0 + 1, next[i].1);
+            }
+            next.push(cell);
+        }
+
+        let (cost, start) = next[query.len()];
+        if cost < best.0 {
+            best = (cost, start..end);
+        }
+        column = next;
+    }
+
+    best
+}
+
+/// Searches for the query in contents with case sensitivity, pairing
+/// each matching line with its 1-based line number.
+///
+/// ## Example
+/// ```
+/// let query = "to";
+/// let contents = "\
+///     To here\n\
+///     but not there.\n\
+///     here to there.";
+///
+/// assert_eq!(vec![(3, "here to there.")],
+///     minigrep::search_with_line_numbers(query, &contents));
+/// ```
+pub fn search_with_line_numbers<'a>(query: &str, contents: &'a str) -> Vec<(usize, &'a str)> {
+    lines(contents)
+        .enumerate()
+        .filter(|(_, line)| line.contains(query))
+        .map(|(index, line)| (index + 1, line))
+        .collect()
+}
+
+/// Searches for the query in contents with case insensitivity, pairing
+/// each matching line with its 1-based line number.
+///
+/// ## Example
+/// ```
+/// let query = "tO";
+/// let contents = "\
+///     To here\n\
+///     but not there.\n\
+///     here to there.";
+///
+/// assert_eq!(vec![(1, "To here"), (3, "here to there.")],
+///     minigrep::search_case_insensitive_with_line_numbers(query, &contents));
+/// ```
+pub fn search_case_insensitive_with_line_numbers<'a>(
+    query: &str,
+    contents: &'a str,
+) -> Vec<(usize, &'a str)> {
+    let query = query.to_lowercase();
+
+    lines(contents)
+        .enumerate()
+        .filter(|(_, line)| line.to_lowercase().contains(&query))
+        .map(|(index, line)| (index + 1, line))
+        .collect()
+}
+
+/// Searches for the query in contents with case sensitivity, and returns
+/// each match along with up to `before` preceding and `after` following
+/// lines, as `(line_number, line)` pairs with 1-based line numbers.
+///
+/// Matches whose context windows overlap or touch are merged into one
+/// group, so every line appears at most once. Separate groups aren't
+/// contiguous in the original contents.
+///
+/// ## Example
+/// ```
+/// let query = "to";
+/// let contents = "\
+///     here to there.\n\
+///     but not there.\n\
+///     nothing\n\
+///     nothing\n\
+///     up to you.";
+///
+/// assert_eq!(
+///     vec![
+///         vec![(1, "here to there."), (2, "but not there.")],
+///         vec![(4, "nothing"), (5, "up to you.")],
+///     ],
+///     minigrep::search_with_context(query, &contents, 1, 1)
+/// );
+/// ```
+pub fn search_with_context<'a>(
+    query: &str,
+    contents: &'a str,
+    before: usize,
+    after: usize,
+) -> Vec<Vec<(usize, &'a str)>> {
+    let lines: Vec<&str> = lines(contents).collect();
+    let matched: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.contains(query))
+        .map(|(index, _)| index)
+        .collect();
+
+    context_groups(&matched, lines.len(), before, after)
+        .into_iter()
+        .map(|group| group.map(|index| (index + 1, lines[index])).collect())
+        .collect()
+}
+
+/// Turns the sorted indices of matching lines into ranges of line indices
+/// to print, merging windows that overlap or are adjacent.
+fn context_groups(
+    matched: &[usize],
+    line_count: usize,
+    before: usize,
+    after: usize,
+) -> Vec<Range<usize>> {
+    let mut groups: Vec<Range<usize>> = Vec::new();
+
+    for &index in matched {
+        let start = index.saturating_sub(before);
+        let end = (index + after + 1).min(line_count);
+
+        match groups.last_mut() {
+            Some(last) if last.end >= start => last.end = last.end.max(end),
+            _ => groups.push(start..end),
+        }
+    }
+
+    groups
+}
+
+/// Searches for the query in contents as a whole word, with case
+/// sensitivity. The query only matches where it isn't directly preceded
+/// or followed by a word character (`[A-Za-z0-9_]`).
+///
+/// ## Example
+/// ```
+/// let query = "cat";
+/// let contents = "\
+///     a category\n\
+///     concatenate\n\
+///     the cat sat\n\
+///     cat";
+///
+/// assert_eq!(vec!["the cat sat", "cat"], minigrep::search_word(query, &contents));
+/// ```
+pub fn search_word<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    lines(contents)
+        .filter(|line| contains_word(line, query))
+        .collect()
+}
+
+/// Searches for the query in contents as a whole word, with case
+/// insensitivity.
+///
+/// ## Example
+/// ```
+/// let query = "CAT";
+/// let contents = "\
+///     Cat food\n\
+///     category";
+///
+/// assert_eq!(vec!["Cat food"],
+///     minigrep::search_word_case_insensitive(query, &contents));
+/// ```
+pub fn search_word_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let query = query.to_lowercase();
+
+    lines(contents)
+        .filter(|line| contains_word(&line.to_lowercase(), &query))
+        .collect()
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Whether any occurrence of the query in the line is bounded by a
+/// non-word character or the start/end of the line on both sides.
+fn contains_word(line: &str, query: &str) -> bool {
+    line.match_indices(query)
+        .any(|(start, matched)| is_word_bounded(line, &(start..start + matched.len())))
+}
+
+fn is_word_bounded(line: &str, range: &Range<usize>) -> bool {
+    let before = line[..range.start].chars().next_back();
+    let after = line[range.end..].chars().next();
+
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
+
+/// Byte ranges of every non-overlapping occurrence of the query in the
+/// line, from left to right, for highlighting matches without the ANSI
+/// codes `highlight_matches` uses. Ignoring case, the ranges are still of
+/// the original line, even where lowercasing changes a character's length.
+///
+/// ## Example
+/// ```
+/// let line = "Ünïcode, ünïcode";
+///
+/// assert_eq!(vec![0..5, 11..16], minigrep::match_ranges("ünï", line, false));
+/// assert_eq!(vec![11..16], minigrep::match_ranges("ünï", line, true));
+/// ```
+pub fn match_ranges(query: &str, line: &str, case_sensitive: bool) -> Vec<Range<usize>> {
+    literal_ranges(line, query, case_sensitive)
+}
+
+/// Searches contents for the lines that contain every one of the terms,
+/// in any order, minding case only when `case_sensitive` is set. Every
+/// line matches when there are no terms.
+///
+/// ## Example
+/// ```
+/// let terms = ["disk".to_string(), "error".to_string()];
+/// let contents = "\
+///     Error: disk full\n\
+///     disk checked\n\
+///     error: no disk";
+///
+/// assert_eq!(vec!["error: no disk"], minigrep::search_all(&terms, contents, true));
+/// assert_eq!(vec!["Error: disk full", "error: no disk"],
+///     minigrep::search_all(&terms, contents, false));
+/// ```
+pub fn search_all<'a>(terms: &[String], contents: &'a str, case_sensitive: bool) -> Vec<&'a str> {
+    let terms: Vec<String> = terms
+        .iter()
+        .map(|term| {
+            if case_sensitive {
+                term.to_string()
+            } else {
+                term.to_lowercase()
+            }
+        })
+        .collect();
+
+    lines(contents)
+        .filter(|line| {
+            let line: Cow<str> = if case_sensitive {
+                Cow::Borrowed(line)
+            } else {
+                Cow::Owned(line.to_lowercase())
+            };
+            terms.iter().all(|term| line.contains(term.as_str()))
+        })
+        .collect()
+}
+
+/// Counts the non-overlapping occurrences of the query in the line, going
+/// from left to right, so `aa` occurs twice in `aaaa`, not three times.
+///
+/// ## Example
+/// ```
+/// assert_eq!(2, minigrep::count_occurrences_in_line("aa", "aaaa", true));
+/// assert_eq!(2, minigrep::count_occurrences_in_line("rust", "Rust, not trust", false));
+/// ```
+pub fn count_occurrences_in_line(query: &str, line: &str, case_sensitive: bool) -> usize {
+    match_ranges(query, line, case_sensitive).len()
+}
+
+/// Wraps every occurrence of the query in the line in ANSI color codes.
+///
+/// ## Example
+/// ```
+/// assert_eq!(
+///     "\x1b[01;31mTo\x1b[0m and fro, \x1b[01;31mto\x1b[0m.",
+///     minigrep::highlight_matches("To and fro, to.", "to", false)
+/// );
+/// ```
+pub fn highlight_matches(line: &str, query: &str, case_sensitive: bool) -> String {
+    highlight(
+        line,
+        &literal_ranges(line, query, case_sensitive),
+        DEFAULT_MATCH_COLOR,
+    )
+}
+
+/// Wraps the given sorted, non-overlapping byte ranges of the line in
+/// ANSI color codes, showing them with the given SGR codes.
+fn highlight(line: &str, ranges: &[Range<usize>], color: &str) -> String {
+    let mut highlighted = String::with_capacity(line.len());
+    let mut last = 0;
+
+    for range in ranges {
+        highlighted.push_str(&line[last..range.start]);
+        push_colored(&mut highlighted, &line[range.clone()], color);
+        last = range.end;
+    }
+    highlighted.push_str(&line[last..]);
+
+    highlighted
+}
+
+/// Pushes the text shown with the given SGR codes, resetting the color
+/// after it. Text spanning several lines, like a multiline match, is
+/// colored and reset on each of them, so a pager showing the lines on
+/// their own never lets the color bleed into the next one.
+fn push_colored(out: &mut String, text: &str, color: &str) {
+    for (i, piece) in text.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        if !piece.is_empty() {
+            out.push_str(&format!("\x1b[{}m{}{}", color, piece, HIGHLIGHT_END));
+        }
+    }
+}
+
+/// Searches for the query in contents with case sensitivity, and returns
+/// each matching line with the byte offsets, relative to the start of the
+/// line, of every non-overlapping occurrence of the query in it.
+///
+/// ## Example
+/// ```
+/// let query = "to";
+/// let contents = "\
+///     To here\n\
+///     to and fro, to.";
+///
+/// assert_eq!(vec![("to and fro, to.", vec![0, 12])],
+///     minigrep::search_with_positions(query, &contents));
+/// ```
+pub fn search_with_positions<'a>(query: &str, contents: &'a str) -> Vec<(&'a str, Vec<usize>)> {
+    lines(contents)
+        .map(|line| (line, literal_ranges(line, query, true)))
+        .filter(|(_, ranges)| !ranges.is_empty())
+        .map(|(line, ranges)| (line, ranges.into_iter().map(|r| r.start).collect()))
+        .collect()
+}
+
+/// Searches for the query in contents with case insensitivity, and
+/// returns each matching line with the byte offsets of every occurrence
+/// of the query in it. Matching is done on the lowercased line, but the
+/// offsets are relative to the original line, even where lowercasing
+/// changes the length of a character.
+///
+/// ## Example
+/// ```
+/// let query = "TO";
+/// let contents = "\
+///     Ünter To\n\
+///     nothing here";
+///
+/// assert_eq!(vec![("Ünter To", vec![7])],
+///     minigrep::search_with_positions_case_insensitive(query, &contents));
+/// ```
+pub fn search_with_positions_case_insensitive<'a>(
+    query: &str,
+    contents: &'a str,
+) -> Vec<(&'a str, Vec<usize>)> {
+    lines(contents)
+        .map(|line| (line, literal_ranges(line, query, false)))
+        .filter(|(_, ranges)| !ranges.is_empty())
+        .map(|(line, ranges)| (line, ranges.into_iter().map(|r| r.start).collect()))
+        .collect()
+}
+
+/// Byte ranges of every non-overlapping occurrence of the query in the
+/// line, relative to the original line.
+fn literal_ranges(line: &str, query: &str, case_sensitive: bool) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    if case_sensitive {
+        return line
+            .match_indices(query)
+            .map(|(start, matched)| start..start + matched.len())
+            .collect();
+    }
+
+    // Every byte of the lowercased line remembers the byte range of the
+    // original character it came from.
+    let mut lowered = String::with_capacity(line.len());
+    let mut origins = Vec::with_capacity(line.len());
+    for (start, c) in line.char_indices() {
+        let origin = start..start + c.len_utf8();
+        for lower in c.to_lowercase() {
+            lowered.push(lower);
+            origins.resize(lowered.len(), origin.clone());
+        }
+    }
+
+    // Two matches inside the lowercase form of one character would map
+    // back to the same range, so only the first is kept.
+    let query = query.to_lowercase();
+    let mut last_end = 0;
+    lowered
+        .match_indices(query.as_str())
+        .map(|(start, matched)| origins[start].start..origins[start + matched.len() - 1].end)
+        .filter(|range| {
+            let separate = range.start >= last_end;
+            if separate {
+                last_end = range.end;
+            }
+            separate
+        })
+        .collect()
+}
+
+/// Searches for the lines in contents that don't contain the query,
+/// with case sensitivity.
+///
+/// ## Example
+/// ```
+/// let query = "to";
+/// let contents = "\
+///     To here\n\
+///     but not there.\n\
+///     here to there.";
+///
+/// assert_eq!(vec!["To here", "but not there."],
+///     minigrep::search_invert(query, &contents));
+/// ```
+pub fn search_invert<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    lines(contents)
+        .filter(|line| !line.contains(query))
+        .collect()
+}
+
+/// Searches for the lines in contents that don't contain the query,
+/// with case insensitivity.
+///
+/// ## Example
+/// ```
+/// let query = "tO";
+/// let contents = "\
+///     To here\n\
+///     but not there.\n\
+///     here to there.";
+///
+/// assert_eq!(vec!["but not there."],
+///     minigrep::search_invert_case_insensitive(query, &contents));
+/// ```
+pub fn search_invert_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let query = query.to_lowercase();
+
+    lines(contents)
+        .filter(|line| !line.to_lowercase().contains(&query))
+        .collect()
+}
+
+/// Counts the lines in contents that contain the query.
+///
+/// ## Example
+/// ```
+/// let query = "to";
+/// let contents = "\
+///     To here\n\
+///     but not there.\n\
+///     here to there.";
+///
+/// assert_eq!(1, minigrep::count_matches(query, &contents, true));
+/// assert_eq!(2, minigrep::count_matches(query, &contents, false));
+/// ```
+pub fn count_matches(query: &str, contents: &str, case_sensitive: bool) -> usize {
+    if case_sensitive {
+        search(query, contents).len()
+    } else {
+        search_case_insensitive(query, contents).len()
+    }
+}
+
+/// Searches for the query in contents with case sensitivity, and returns
+/// every occurrence of it rather than the lines it occurs on, so a line
+/// with two occurrences yields two entries.
+///
+/// ## Example
+/// ```
+/// let query = "to";
+/// let contents = "\
+///     To here\n\
+///     to and fro, to.";
+///
+/// assert_eq!(vec!["to", "to"], minigrep::search_only_matching(query, &contents));
+/// ```
+pub fn search_only_matching<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    lines(contents)
+        .flat_map(|line| {
+            literal_ranges(line, query, true)
+                .into_iter()
+                .map(move |range| &line[range])
+        })
+        .collect()
+}
+
+/// Searches for the regular expression pattern in contents, and returns
+/// the text of every non-empty match rather than the lines they occur on.
+///
+/// ## Errors
+/// Returns the compile error if the pattern isn't a valid regular
+/// expression.
+///
+/// ## Example
+/// ```
+/// let pattern = "[0-9]+";
+/// let contents = "\
+///     call 555 1234\n\
+///     no digits here";
+///
+/// assert_eq!(vec!["555", "1234"],
+///     minigrep::search_regex_only_matching(pattern, &contents).unwrap());
+/// ```
+pub fn search_regex_only_matching<'a>(
+    pattern: &str,
+    contents: &'a str,
+) -> Result<Vec<&'a str>, regex::Error> {
+    let re = Regex::new(pattern)?;
+
+    Ok(lines(contents)
+        .flat_map(|line| re.find_iter(line).map(|found| found.as_str()))
+        .filter(|found| !found.is_empty())
+        .collect())
+}
+
+/// Searches for lines in contents matching the regular expression
+/// pattern.
+///
+/// ## Errors
+/// Returns the compile error if the pattern isn't a valid regular
+/// expression.
+///
+/// ## Example
+/// ```
+/// let pattern = "^here|there$";
+/// let contents = "\
+///     To here\n\
+///     but not there.\n\
+///     here to there";
+///
+/// assert_eq!(vec!["here to there"],
+///     minigrep::search_regex(pattern, &contents).unwrap());
+/// assert!(minigrep::search_regex("(unclosed", &contents).is_err());
+/// ```
+pub fn search_regex<'a>(pattern: &str, contents: &'a str) -> Result<Vec<&'a str>, regex::Error> {
+    let re = Regex::new(pattern)?;
+
+    Ok(lines(contents).filter(|line| re.is_match(line)).collect())
+}
+
+/// Checks that the pattern is a valid regular expression without
+/// searching anything with it.
+///
+/// ## Errors
+/// Returns the reason the pattern can't be compiled, pointing at where in
+/// the pattern it goes wrong.
+///
+/// ## Example
+/// ```
+/// assert!(minigrep::validate_regex("^here|there$").is_ok());
+/// assert!(minigrep::validate_regex("(unclosed")
+///     .unwrap_err()
+///     .contains("unclosed group"));
+/// ```
+pub fn validate_regex(pattern: &str) -> Result<(), String> {
+    Regex::new(pattern).map(|_| ()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        let mut all = vec!["minigrep".to_string()];
+        all.extend(args.iter().map(|arg| arg.to_string()));
+        all.into_iter()
+    }
+
+    fn config_error(arguments: &[&str]) -> String {
+        match Config::new(args(arguments)) {
+            Err(MinigrepError::InvalidConfig(message)) => message,
+            _ => panic!("expected {:?} to be an invalid config", arguments),
+        }
+    }
+
+    fn config(query: &str) -> Config {
+        Config {
+            query: query.to_string(),
+            filenames: vec![],
+            case_sensitive: true,
+            show_line_numbers: false,
+            regex: false,
+            invert_match: false,
+            count_only: false,
+            before_context: 0,
+            after_context: 0,
+            word_match: false,
+            format: OutputFormat::Text,
+            max_count: None,
+            color: ColorChoice::Never,
+            recursive: false,
+            only_matching: false,
+            fixed_string: false,
+            threads: None,
+            stream: false,
+            files_with_matches: false,
+            files_without_match: false,
+            binary_files: BinaryFiles::Skip,
+            quiet: false,
+            stats: false,
+            multiline: false,
+            byte_offset: false,
+            fuzzy: None,
+            max_line_length: None,
+            patterns: Vec::new(),
+            field: None,
+            delimiter: ",".to_string(),
+            vimgrep: false,
+            check: false,
+            line_range: None,
+            mmap: false,
+            replace: None,
+            in_place: false,
+            verbose: false,
+            null: false,
+            null_data: false,
+            starts_with: false,
+            ends_with: false,
+            match_color: DEFAULT_MATCH_COLOR.to_string(),
+            group: false,
+            with_filename: None,
+            timeout: None,
+            decompress: false,
+            smart_case: false,
+            ignore_whitespace: false,
+            skip_blank: false,
+            shell_escape: false,
+            unique: false,
+            exclude_pattern: None,
+            debug_config: false,
+            progress: false,
+            paragraph: false,
+            max_depth: None,
+            include: vec![],
+            exclude: vec![],
+            hidden: false,
+            block: false,
+            lines: None,
+            span: false,
+            since: None,
+            until: None,
+            keep_untimed: false,
+            line_delimiter: None,
+            count_per_line: false,
+            all_terms: false,
+            follow: false,
+            absolute_path: false,
+            match_filename: false,
+            tabs: None,
+            heading_pattern: None,
+            sort: SortOrder::None,
+            retries: None,
+            strip_ansi: false,
+            print_original: false,
+            join: None,
+            encoding: None,
+            relative_to: None,
+            partial: false,
+        }
+    }
+
+    #[test]
+    fn one_result() {
+        let query = "duct";
+        let contents = "\
+            Rust:\n\
+            safe, fast, productive.\n\
+            Pick three.\n\
+            Duct tape.";
+
+        assert_eq!(vec!["safe, fast, productive."], search(query, contents));
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let query = "rUsT";
+        let contents = "\
+            Rust:\n\
+            safe, fast, productive.\n\
+            Pick three.\n\
+            Trust me.";
+
+        assert_eq!(
+            vec!["Rust:", "Trust me."],
+            search_case_insensitive(query, contents)
+        );
+    }
+
+    #[test]
+    fn line_numbers_for_repeated_lines() {
+        let query = "duct";
+        let contents = "\
+            Duct tape.\n\
+            productive.\n\
+            Pick three.\n\
+            productive.";
+
+        assert_eq!(
+            vec![(2, "productive."), (4, "productive.")],
+            search_with_line_numbers(query, contents)
+        );
+    }
+
+    #[test]
+    fn line_numbers_case_insensitive() {
+        let query = "dUcT";
+        let contents = "\
+            Duct tape.\n\
+            Pick three.\n\
+            Duct tape.";
+
+        assert_eq!(
+            vec![(1, "Duct tape."), (3, "Duct tape.")],
+            search_case_insensitive_with_line_numbers(query, contents)
+        );
+    }
+
+    #[test]
+    fn regex_character_class() {
+        let pattern = "[0-9]{3}";
+        let contents = "\
+            call 555 1234\n\
+            no digits here\n\
+            only 12";
+
+        assert_eq!(
+            vec!["call 555 1234"],
+            search_regex(pattern, contents).unwrap()
+        );
+    }
+
+    #[test]
+    fn regex_matcher_honors_case_insensitivity() {
+        let mut config = config("^rust");
+        config.case_sensitive = false;
+        config.regex = true;
+        let matcher = Matcher::new(&config).unwrap();
+
+        assert!(matcher.is_match("Rust:"));
+        assert!(!matcher.is_match("Trust me."));
+    }
+
+    #[test]
+    fn invert_case_insensitive() {
+        let query = "rUsT";
+        let contents = "\
+            Rust:\n\
+            safe, fast, productive.\n\
+            Trust me.";
+
+        assert_eq!(
+            vec!["safe, fast, productive."],
+            search_invert_case_insensitive(query, contents)
+        );
+        assert_eq!(
+            vec!["Rust:", "safe, fast, productive.", "Trust me."],
+            search_invert(query, contents)
+        );
+    }
+
+    #[test]
+    fn run_collect_formats_prefixes() {
+        let dir = env::temp_dir().join("minigrep_run_collect_formats_prefixes");
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.txt");
+        let second = dir.join("second.txt");
+        fs::write(&first, "Rust:\nTrust me.\n").unwrap();
+        fs::write(&second, "Pick three.\nrust\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![
+            first.to_string_lossy().into_owned(),
+            second.to_string_lossy().into_owned(),
+        ];
+        config.show_line_numbers = true;
+
+        assert_eq!(
+            vec![
+                format!("{}:2:Trust me.", first.display()),
+                format!("{}:2:rust", second.display()),
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        config.count_only = true;
+        assert_eq!(
+            vec![
+                format!("{}:1", first.display()),
+                format!("{}:1", second.display()),
+            ],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn matches_are_only_separated_with_context() {
+        let dir = env::temp_dir().join("minigrep_matches_are_only_separated_with_context");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "rust\na\nb\nc\nrust\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        assert_eq!(vec!["rust", "rust"], run_collect(&config).unwrap());
+
+        config.after_context = 1;
+        assert_eq!(
+            vec!["rust", "a", "--", "rust"],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn context_groups_merge_overlapping_windows() {
+        assert_eq!(vec![0..6], context_groups(&[1, 4], 10, 1, 1));
+        assert_eq!(vec![0..2, 4..6], context_groups(&[1, 5], 10, 1, 0));
+        assert_eq!(vec![7..10], context_groups(&[9], 10, 2, 5));
+    }
+
+    #[test]
+    fn context_keeps_line_numbers() {
+        let dir = env::temp_dir().join("minigrep_context_keeps_line_numbers");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "a\nb\nmatch\nc\nd\ne\nmatch\n").unwrap();
+
+        let mut config = config("match");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.show_line_numbers = true;
+        config.before_context = 1;
+        config.after_context = 1;
+
+        assert_eq!(
+            vec!["2-b", "3:match", "4-c", "--", "6-e", "7:match"],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn context_lines_are_separated_with_dashes() {
+        let dir = env::temp_dir().join("minigrep_context_lines_are_separated_with_dashes");
+        fs::create_dir_all(&dir).unwrap();
+        let poem = dir.join("poem.txt");
+        fs::write(&poem, "one\ntwo\nmatch\nfour\n").unwrap();
+        let notes = dir.join("notes.txt");
+        fs::write(&notes, "match\nnothing\n").unwrap();
+        let poem = poem.to_string_lossy().into_owned();
+        let notes = notes.to_string_lossy().into_owned();
+
+        let config = Config::new(args(&["-C", "1", "-n", "match", &poem])).unwrap();
+        assert_eq!(
+            vec!["2-two", "3:match", "4-four"],
+            run_collect(&config).unwrap()
+        );
+
+        let config = Config::new(args(&["-C", "1", "-n", "match", &poem, &notes])).unwrap();
+        assert_eq!(
+            vec![
+                format!("{}-2-two", poem),
+                format!("{}:3:match", poem),
+                format!("{}-4-four", poem),
+                format!("{}:1:match", notes),
+                format!("{}-2-nothing", notes),
+            ],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn read_contents_from_reader() {
+        let reader = io::Cursor::new("Rust:\nTrust me.\n");
+
+        assert_eq!("Rust:\nTrust me.\n", read_contents(reader, false).unwrap());
+    }
+
+    #[test]
+    fn stdin_display_name() {
+        assert_eq!("(standard input)", display_name(STDIN_FILENAME));
+        assert_eq!("poem.txt", display_name("poem.txt"));
+    }
+
+    #[test]
+    fn config_parses_query_and_filenames() {
+        let config = Config::new(args(&["-n", "duct", "poem.txt", "notes.txt"])).unwrap();
+
+        assert_eq!("duct", config.query);
+        assert_eq!(vec!["poem.txt", "notes.txt"], config.filenames);
+        assert!(config.show_line_numbers);
+    }
+
+    #[test]
+    fn config_missing_query() {
+        assert_eq!("Didn't get a query string", config_error(&[]));
+    }
+
+    #[test]
+    fn config_missing_filename_reads_stdin() {
+        let config = Config::new(args(&["duct"])).unwrap();
+
+        assert_eq!(vec![STDIN_FILENAME], config.filenames);
+    }
+
+    #[test]
+    fn config_missing_option_value() {
+        assert_eq!(
+            "Didn't get a number for the option",
+            config_error(&["duct", "-A"])
+        );
+        assert_eq!(
+            "Expected a number after the option",
+            config_error(&["-C", "two", "duct"])
+        );
+    }
+
+    #[test]
+    fn word_match_at_line_edges() {
+        assert!(contains_word("cat", "cat"));
+        assert!(contains_word("cat, dog", "cat"));
+        assert!(contains_word("dog-cat", "cat"));
+        assert!(!contains_word("cat_dog", "cat"));
+        assert!(!contains_word("concatenate", "cat"));
+        assert!(contains_word("concat cat", "cat"));
+    }
+
+    #[test]
+    fn word_matcher_composes_with_case_and_regex() {
+        let mut config = config("CAT");
+        config.case_sensitive = false;
+        config.word_match = true;
+        let matcher = Matcher::new(&config).unwrap();
+
+        assert!(matcher.is_match("The Cat sat."));
+        assert!(!matcher.is_match("Category"));
+
+        config.query = "c.t".to_string();
+        config.regex = true;
+        let matcher = Matcher::new(&config).unwrap();
+
+        assert!(matcher.is_match("The Cut sat."));
+        assert!(!matcher.is_match("Cutlery"));
+    }
+
+    #[test]
+    fn json_output_round_trips() {
+        let dir = env::temp_dir().join("minigrep_json_output_round_trips");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "Rust:\nsafe, fast, productive.\nTrust me.\n").unwrap();
+        let filename = file.to_string_lossy().into_owned();
+
+        let mut config = config("rust");
+        config.case_sensitive = false;
+        config.filenames = vec![filename.clone()];
+        config.format = OutputFormat::Json;
+        config.show_line_numbers = true;
+
+        let results = run_collect(&config).unwrap();
+        assert_eq!(1, results.len());
+
+        let parsed: serde_json::Value = serde_json::from_str(&results[0]).unwrap();
+        assert_eq!(
+            serde_json::json!([
+                {"file": filename, "line_number": 1, "text": "Rust:"},
+                {"file": filename, "line_number": 3, "text": "Trust me."},
+            ]),
+            parsed
+        );
+
+        config.show_line_numbers = false;
+        let parsed: serde_json::Value =
+            serde_json::from_str(&run_collect(&config).unwrap()[0]).unwrap();
+        assert!(parsed[0].get("line_number").is_none());
+        assert_eq!("Trust me.", parsed[1]["text"]);
+
+        for flag in ["-c", "-l", "-L"] {
+            assert!(config_error(&["--format", "json", flag, "rust"])
+                .starts_with("--format json can't be used with"));
+        }
+    }
+
+    #[test]
+    fn limited_returns_first_matches() {
+        let contents: String = (1..=100).map(|i| format!("match {}\n", i)).collect();
+
+        assert_eq!(
+            vec!["match 1", "match 2", "match 3", "match 4", "match 5"],
+            search_limited("match", &contents, 5)
+        );
+    }
+
+    #[test]
+    fn positions_report_every_occurrence() {
+        assert_eq!(
+            vec![("a to b to c to", vec![2, 7, 12])],
+            search_with_positions("to", "a to b to c to\nnone")
+        );
+    }
+
+    #[test]
+    fn positions_case_insensitive_map_to_original_offsets() {
+        // 'İ' is two bytes but lowercases to three, shifting later offsets.
+        assert_eq!(
+            vec![("İx Rust rust", vec![4, 9])],
+            search_with_positions_case_insensitive("RUST", "İx Rust rust")
+        );
+        assert_eq!(vec![0..2], literal_ranges("İx", "i̇", false));
+    }
+
+    #[test]
+    fn highlight_every_match() {
+        assert_eq!(
+            "\x1b[01;31mduct\x1b[0m and \x1b[01;31mduct\x1b[0m tape",
+            highlight_matches("duct and duct tape", "duct", true)
+        );
+        assert_eq!("no match", highlight_matches("no match", "duct", true));
+    }
+
+    #[test]
+    fn highlight_regex_and_word_ranges() {
+        let mut regex_config = config("[0-9]+");
+        regex_config.regex = true;
+        let matcher = Matcher::new(&regex_config).unwrap();
+        assert_eq!(vec![5..8, 9..13], matcher.find_ranges("call 555 1234"));
+
+        let mut word_config = config("cat");
+        word_config.word_match = true;
+        let matcher = Matcher::new(&word_config).unwrap();
+        assert_eq!(vec![12..15], matcher.find_ranges("concatenate cat"));
+    }
+
+    #[test]
+    fn missing_file_is_reported_by_name() {
+        let mut config = config("duct");
+        config.filenames = vec!["file_that_does_not_exists.txt".to_string()];
+
+        match run_collect(&config) {
+            Err(MinigrepError::FileNotFound(filename)) => {
+                assert_eq!("file_that_does_not_exists.txt", filename)
+            }
+            _ => panic!("expected a missing file error"),
+        }
+    }
+
+    #[test]
+    fn recursive_search_prefixes_paths_and_skips_binary() {
+        let root = env::temp_dir().join("minigrep_recursive_search");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("nested")).unwrap();
+        fs::write(root.join("top.txt"), "rust at the top\n").unwrap();
+        fs::write(root.join("nested/deep.txt"), "nothing\nrust below\n").unwrap();
+        fs::write(root.join("nested/binary.bin"), b"rust \xff\xfe\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![root.to_string_lossy().into_owned()];
+        config.recursive = true;
+
+        assert_eq!(
+            vec![
+                format!("{}:rust below", root.join("nested/deep.txt").display()),
+                format!("{}:rust at the top", root.join("top.txt").display()),
+            ],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn only_matching_prints_each_occurrence() {
+        let dir = env::temp_dir().join("minigrep_only_matching");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("numbers.txt");
+        fs::write(&file, "call 555 1234\nno digits\nroom 101\n").unwrap();
+
+        let mut config = config("[0-9]+");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.regex = true;
+        config.only_matching = true;
+        config.show_line_numbers = true;
+
+        assert_eq!(
+            vec!["1:555", "1:1234", "3:101"],
+            run_collect(&config).unwrap()
+        );
+
+        config.query = "o".to_string();
+        config.regex = false;
+        config.show_line_numbers = false;
+        assert_eq!(vec!["o", "o", "o"], run_collect(&config).unwrap());
+    }
+
+    #[test]
+    fn fixed_string_overrides_regex() {
+        let config = Config::new(args(&["-e", "a.b.c", "-F", "file.txt"])).unwrap();
+        assert!(config.regex && config.fixed_string);
+
+        let matcher = Matcher::new(&config).unwrap();
+        assert!(matcher.is_match("see a.b.c here"));
+        assert!(!matcher.is_match("axbxc"));
+    }
+
+    #[test]
+    fn parallel_search_keeps_input_order() {
+        let dir = env::temp_dir().join("minigrep_parallel_search_keeps_input_order");
+        fs::create_dir_all(&dir).unwrap();
+        let mut filenames = Vec::new();
+        for i in 0..32 {
+            let file = dir.join(format!("{}.txt", i));
+            fs::write(&file, format!("match {}\nskip\nmatch {} again\n", i, i)).unwrap();
+            filenames.push(file.to_string_lossy().into_owned());
+        }
+        filenames.push(dir.join("missing.txt").to_string_lossy().into_owned());
+
+        let mut config = config("match");
+        config.filenames = filenames;
+        config.threads = Some(1);
+        let mut sequential = Vec::new();
+        assert!(search_files(&config, &mut sequential).is_err());
+
+        config.threads = Some(4);
+        let mut parallel = Vec::new();
+        assert!(search_files(&config, &mut parallel).is_err());
+
+        assert_eq!(64, parallel.len());
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn for_each_match_strips_line_endings() {
+        let reader = io::Cursor::new("rust\r\ntrust me\nno\nrusty");
+        let mut matches = Vec::new();
+
+        for_each_match(reader, "rust", |line| matches.push(line.to_string())).unwrap();
+
+        assert_eq!(vec!["rust", "trust me", "rusty"], matches);
+    }
+
+    #[test]
+    fn streaming_matches_buffered_search_on_large_file() {
+        let dir = env::temp_dir().join("minigrep_streaming_large_file");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("large.log");
+        let mut contents = String::new();
+        for i in 0..200_000 {
+            if i % 1000 == 0 {
+                contents.push_str(&format!("{} ERROR something failed\n", i));
+            } else {
+                contents.push_str(&format!("{} INFO all good\n", i));
+            }
+        }
+        fs::write(&file, contents).unwrap();
+
+        let mut config = config("ERROR");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.show_line_numbers = true;
+        let buffered = run_collect(&config).unwrap();
+
+        config.stream = true;
+        let streamed = run_collect(&config).unwrap();
+        assert_eq!(200, streamed.len());
+        assert_eq!(buffered, streamed);
+
+        config.count_only = true;
+        config.max_count = Some(5);
+        assert_eq!(vec!["5"], run_collect(&config).unwrap());
+    }
+
+    #[test]
+    fn files_with_and_without_matches() {
+        let root = env::temp_dir().join("minigrep_files_with_and_without_matches");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.txt"), "rust\nrust again\n").unwrap();
+        fs::write(root.join("b.txt"), "nothing\n").unwrap();
+        fs::write(root.join("c.txt"), "more\nrust\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![root.to_string_lossy().into_owned()];
+        config.recursive = true;
+        config.files_with_matches = true;
+        assert_eq!(
+            vec![
+                root.join("a.txt").display().to_string(),
+                root.join("c.txt").display().to_string(),
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        config.files_with_matches = false;
+        config.files_without_match = true;
+        assert_eq!(
+            vec![root.join("b.txt").display().to_string()],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn command_line_overrides_defaults() {
+        let defaults = Defaults {
+            query: Some("TODO".to_string()),
+            case_sensitive: Some(false),
+            color: Some(ColorChoice::Always),
+            context: Some(2),
+        };
+        let config =
+            Config::with_defaults(args(&["--color", "never", "-A", "1", "fixme"]), defaults)
+                .unwrap();
+
+        assert_eq!("fixme", config.query);
+        assert!(!config.case_sensitive);
+        assert_eq!(ColorChoice::Never, config.color);
+        assert_eq!(2, config.before_context);
+        assert_eq!(1, config.after_context);
+
+        let config = Config::with_defaults(
+            args(&[]),
+            Defaults {
+                query: Some("TODO".to_string()),
+                ..Defaults::default()
+            },
+        )
+        .unwrap();
+        assert_eq!("TODO", config.query);
+        assert_eq!(vec![STDIN_FILENAME], config.filenames);
+    }
+
+    #[test]
+    fn binary_files_modes() {
+        let dir = env::temp_dir().join("minigrep_binary_files_modes");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("latin1.log");
+        fs::write(&file, b"caf\xe9 error\nfine\n").unwrap();
+
+        let mut config = config("error");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+
+        config.binary_files = BinaryFiles::Lossy;
+        assert_eq!(vec!["caf\u{fffd} error"], run_collect(&config).unwrap());
+        config.stream = true;
+        assert_eq!(vec!["caf\u{fffd} error"], run_collect(&config).unwrap());
+
+        config.binary_files = BinaryFiles::Skip;
+        assert!(run_collect(&config).unwrap().is_empty());
+        assert_eq!(
+            vec![(config.filenames[0].clone(), 0)],
+            count_matches_per_file(&config).unwrap()
+        );
+
+        config.binary_files = BinaryFiles::Text;
+        assert_eq!(vec!["caf\u{fffd} error"], run_collect(&config).unwrap());
+        config.stream = false;
+        assert_eq!(vec!["caf\u{fffd} error"], run_collect(&config).unwrap());
+        config.mmap = true;
+        assert_eq!(vec!["caf\u{fffd} error"], run_collect(&config).unwrap());
+        assert_eq!(
+            vec![(config.filenames[0].clone(), 1)],
+            count_matches_per_file(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn long_options_take_inline_values() {
+        let config = Config::new(args(&["--binary-files=lossy", "--max-count=3", "q"])).unwrap();
+
+        assert_eq!(BinaryFiles::Lossy, config.binary_files);
+        assert_eq!(Some(3), config.max_count);
+        assert_eq!(
+            "Option --count doesn't take a value",
+            config_error(&["--count=2", "q"])
+        );
+    }
+
+    #[test]
+    fn quiet_reports_whether_anything_matched() {
+        let dir = env::temp_dir().join("minigrep_quiet_reports");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "Rust:\nTrust me.\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![
+            dir.join("missing.txt").to_string_lossy().into_owned(),
+            file.to_string_lossy().into_owned(),
+        ];
+        config.quiet = true;
+
+        let mut results = Vec::new();
+        assert!(search_files(&config, &mut results).unwrap());
+        assert!(results.is_empty());
+
+        config.query = "nowhere".to_string();
+        assert!(search_files(&config, &mut results).is_err());
+
+        config.filenames.remove(0);
+        assert!(!search_files(&config, &mut results).unwrap());
+    }
+
+    #[test]
+    fn lines_strip_every_terminator() {
+        assert_eq!(
+            vec!["a", "b", "c"],
+            lines("a\r\nb\nc\r").collect::<Vec<_>>()
+        );
+        assert_eq!(vec!["a", ""], lines("a\n\n").collect::<Vec<_>>());
+        assert_eq!(Vec::<&str>::new(), lines("").collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn crlf_files_match_like_lf_files() {
+        let dir = env::temp_dir().join("minigrep_crlf_files");
+        fs::create_dir_all(&dir).unwrap();
+        let lf = dir.join("lf.txt");
+        let crlf = dir.join("crlf.txt");
+        fs::write(&lf, "to be there\nhere to\nnot here\r").unwrap();
+        fs::write(&crlf, "to be there\r\nhere to\r\nnot here\r").unwrap();
+
+        for (query, regex, word) in &[("here$", true, false), ("to", false, true)] {
+            let mut config = config(query);
+            config.regex = *regex;
+            config.word_match = *word;
+
+            config.filenames = vec![lf.to_string_lossy().into_owned()];
+            let expected = run_collect(&config).unwrap();
+            config.filenames = vec![crlf.to_string_lossy().into_owned()];
+            assert_eq!(expected, run_collect(&config).unwrap());
+            config.stream = true;
+            assert_eq!(expected, run_collect(&config).unwrap());
+        }
+    }
+
+    #[test]
+    fn case_flags_anywhere_in_arguments() {
+        assert!(
+            !Config::new(args(&["duct", "-i", "poem.txt"]))
+                .unwrap()
+                .case_sensitive
+        );
+        assert!(
+            !Config::new(args(&["duct", "poem.txt", "--ignore-case"]))
+                .unwrap()
+                .case_sensitive
+        );
+
+        let defaults = Defaults {
+            case_sensitive: Some(false),
+            ..Defaults::default()
+        };
+        let config = Config::with_defaults(args(&["-s", "duct"]), defaults).unwrap();
+        assert!(config.case_sensitive);
+
+        // The last flag given wins.
+        assert!(
+            Config::new(args(&["-i", "--case-sensitive", "duct"]))
+                .unwrap()
+                .case_sensitive
+        );
+    }
+
+    #[test]
+    fn options_in_any_position() {
+        for arguments in &[
+            ["-n", "-c", "duct", "poem.txt"],
+            ["duct", "-n", "poem.txt", "-c"],
+            ["duct", "poem.txt", "-c", "-n"],
+        ] {
+            let config = Config::new(args(arguments)).unwrap();
+
+            assert_eq!("duct", config.query);
+            assert_eq!(vec!["poem.txt"], config.filenames);
+            assert!(config.show_line_numbers && config.count_only);
+        }
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        let config = Config::new(args(&["-n", "--", "-c", "-", "--x"])).unwrap();
+
+        assert_eq!("-c", config.query);
+        assert_eq!(vec!["-", "--x"], config.filenames);
+        assert!(config.show_line_numbers && !config.count_only);
+        assert_eq!("Unknown option -x", config_error(&["-x", "duct"]));
+        assert_eq!("Unknown option --x", config_error(&["duct", "--x=1"]));
+    }
+
+    #[test]
+    fn stats_summary_counts_matches_and_files() {
+        let dir = env::temp_dir().join("minigrep_stats_summary");
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.txt");
+        let second = dir.join("second.txt");
+        fs::write(&first, "rust\nrust\nrust\nother\n").unwrap();
+        fs::write(&second, "nothing\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![
+            first.to_string_lossy().into_owned(),
+            second.to_string_lossy().into_owned(),
+        ];
+        config.stats = true;
+        config.count_only = true;
+        assert_eq!(
+            "3 matches in 1 file (2 files searched)",
+            run_collect(&config).unwrap()[2]
+        );
+
+        config.max_count = Some(2);
+        assert_eq!(
+            "2 matches in 1 file (2 files searched)",
+            run_collect(&config).unwrap()[2]
+        );
+
+        config.max_count = None;
+        config.invert_match = true;
+        assert_eq!(
+            "2 matches in 2 files (2 files searched)",
+            run_collect(&config).unwrap()[2]
+        );
+    }
+
+    #[test]
+    fn glob_patterns_expand_to_files() {
+        let dir = env::temp_dir().join("minigrep_glob_patterns");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.log"), "rust in a\n").unwrap();
+        fs::write(dir.join("b.log"), "rust in b\n").unwrap();
+        fs::write(dir.join("c.txt"), "rust in c\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![dir.join("*.log").to_string_lossy().into_owned()];
+        assert_eq!(
+            vec![
+                format!("{}:rust in a", dir.join("a.log").display()),
+                format!("{}:rust in b", dir.join("b.log").display()),
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        let unmatched = dir.join("*.md").to_string_lossy().into_owned();
+        config.filenames = vec![unmatched.clone()];
+        match run_collect(&config) {
+            Err(MinigrepError::UnmatchedPattern(pattern)) => assert_eq!(unmatched, pattern),
+            _ => panic!("expected an unmatched pattern error"),
+        }
+
+        assert_eq!(vec!["plain.txt"], expand_glob("plain.txt").unwrap());
+    }
+
+    #[test]
+    fn search_iter_is_lazy() {
+        let contents = "rust\nno\nrusty\ntrust";
+        let mut found = search_iter("rust", contents);
+
+        assert_eq!(Some("rust"), found.next());
+        assert_eq!(vec!["rusty", "trust"], found.collect::<Vec<_>>());
+        assert_eq!(
+            Some("trust"),
+            search_case_insensitive_iter("RUST", contents).find(|line| line.starts_with('t'))
+        );
+    }
+
+    #[test]
+    fn multiline_matches_span_lines() {
+        let dir = env::temp_dir().join("minigrep_multiline_matches_span_lines");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("main.rs");
+        fs::write(&file, "fn main() {\n    body();\n}\nfn other() {}\n").unwrap();
+
+        let mut config = config(r"\{\n\s+body");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.regex = true;
+        config.multiline = true;
+        config.show_line_numbers = true;
+        assert_eq!(
+            vec!["1:fn main() {", "2:    body();"],
+            run_collect(&config).unwrap()
+        );
+
+        config.color = ColorChoice::Always;
+        assert_eq!(
+            vec![
+                "1:fn main() \x1b[01;31m{\x1b[0m",
+                "2:\x1b[01;31m    body\x1b[0m();"
+            ],
+            run_collect(&config).unwrap()
+        );
+        config.color = ColorChoice::Never;
+
+        config.only_matching = true;
+        assert_eq!(vec!["1:{\n    body"], run_collect(&config).unwrap());
+
+        config.only_matching = false;
+        config.query = r"(?s)body.*other".to_string();
+        assert_eq!(
+            vec!["2:    body();", "3:}", "4:fn other() {}"],
+            run_collect(&config).unwrap()
+        );
+
+        config.query = r"^\}\n".to_string();
+        config.count_only = true;
+        assert_eq!(vec!["1"], run_collect(&config).unwrap());
+    }
+
+    #[test]
+    fn multiline_needs_regex() {
+        assert_eq!(
+            "--multiline only works with --regex",
+            config_error(&["-U", "a\\nb"])
+        );
+        assert!(Config::new(args(&["-U", "-e", "a\\nb"])).unwrap().multiline);
+    }
+
+    #[test]
+    fn byte_offsets_count_line_terminators() {
+        let dir = env::temp_dir().join("minigrep_byte_offsets_count_line_terminators");
+        fs::create_dir_all(&dir).unwrap();
+        let unix = dir.join("unix.txt");
+        let windows = dir.join("windows.txt");
+        fs::write(&unix, "Rust:\nsafe, fast\nTrust me.\n").unwrap();
+        fs::write(&windows, "Rust:\r\nsafe, fast\r\nTrust me.\r\n").unwrap();
+
+        let mut config = config("ust");
+        config.byte_offset = true;
+        config.filenames = vec![unix.to_string_lossy().into_owned()];
+        assert_eq!(
+            vec!["0:Rust:", "17:Trust me."],
+            run_collect(&config).unwrap()
+        );
+
+        config.filenames = vec![windows.to_string_lossy().into_owned()];
+        assert_eq!(
+            vec!["0:Rust:", "19:Trust me."],
+            run_collect(&config).unwrap()
+        );
+
+        config.stream = true;
+        config.show_line_numbers = true;
+        assert_eq!(
+            vec!["1:0:Rust:", "3:19:Trust me."],
+            run_collect(&config).unwrap()
+        );
+
+        config.only_matching = true;
+        assert_eq!(vec!["1:1:ust", "3:21:ust"], run_collect(&config).unwrap());
+    }
+
+    #[test]
+    fn fuzzy_matches_within_distance() {
+        let contents = "Pick a color.\nPick a number.";
+
+        assert_eq!(vec!["Pick a color."], search_fuzzy("colour", contents, 1));
+        assert!(search_fuzzy("colour", contents, 0).is_empty());
+        assert_eq!(vec!["Pick a color."], search_fuzzy("color", contents, 0));
+
+        assert_eq!((1, 7..12), fuzzy_find("colour", "Pick a color.", true));
+        assert_eq!((0, 7..12), fuzzy_find("COLOR", "Pick a color.", false));
+    }
+
+    #[test]
+    fn fuzzy_option_highlights_closest_part() {
+        let dir = env::temp_dir().join("minigrep_fuzzy_option_highlights_closest_part");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("colors.txt");
+        fs::write(&file, "Pick a color.\nPick a number.\n").unwrap();
+
+        let mut config = Config::new(args(&["--fuzzy=1", "-o", "colour"])).unwrap();
+        assert_eq!(Some(1), config.fuzzy);
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        assert_eq!(vec!["color"], run_collect(&config).unwrap());
+
+        assert!(config_error(&["--fuzzy", "one", "colour"]).contains("number"));
+    }
+
+    #[test]
+    fn long_lines_are_truncated() {
+        let dir = env::temp_dir().join("minigrep_long_lines_are_truncated");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("minified.js");
+        fs::write(&file, "var ünïcode = 1;\nshort\n").unwrap();
+
+        let mut config = config("code");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.max_line_length = Some(8);
+        assert_eq!(vec!["var ünïc..."], run_collect(&config).unwrap());
+
+        config.query = "short".to_string();
+        assert_eq!(vec!["short"], run_collect(&config).unwrap());
+
+        config.query = "= 1".to_string();
+        config.count_only = true;
+        assert_eq!(vec!["1"], run_collect(&config).unwrap());
+    }
+
+    #[test]
+    fn patterns_file_adds_queries() {
+        let dir = env::temp_dir().join("minigrep_patterns_file_adds_queries");
+        fs::create_dir_all(&dir).unwrap();
+        let patterns = dir.join("patterns.txt");
+        let file = dir.join("poem.txt");
+        fs::write(&patterns, "dreary\nfrog\n\nbog\r\n").unwrap();
+        fs::write(
+            &file,
+            "How dreary to be somebody!\nHow public, like a frog\nTo an admiring bog!\n",
+        )
+        .unwrap();
+
+        let mut config = Config::new(args(&[
+            "-f",
+            &patterns.to_string_lossy(),
+            &file.to_string_lossy(),
+        ]))
+        .unwrap();
+        assert_eq!("dreary", config.query);
+        assert_eq!(vec!["frog", "bog"], config.patterns);
+        assert_eq!(vec![file.to_string_lossy()], config.filenames);
+        assert_eq!(
+            vec![
+                "How dreary to be somebody!",
+                "How public, like a frog",
+                "To an admiring bog!"
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        let inline = Config::new(args(&[
+            "-p",
+            "somebody",
+            "-f",
+            &patterns.to_string_lossy(),
+            "--pattern=public",
+            &file.to_string_lossy(),
+        ]))
+        .unwrap();
+        assert_eq!("somebody", inline.query);
+        assert_eq!(vec!["dreary", "frog", "bog", "public"], inline.patterns);
+        assert_eq!(vec![file.to_string_lossy()], inline.filenames);
+        assert_eq!("Didn't get a pattern", config_error(&["-p"]));
+
+        config.only_matching = true;
+        config.query = "a frog".to_string();
+        assert_eq!(vec!["a frog", "bog"], run_collect(&config).unwrap());
+
+        config.only_matching = false;
+        config.regex = true;
+        config.query = "^How d".to_string();
+        assert_eq!(
+            vec![
+                "How dreary to be somebody!",
+                "How public, like a frog",
+                "To an admiring bog!"
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        let missing = dir.join("missing.txt").to_string_lossy().into_owned();
+        assert!(matches!(
+            Config::new(args(&["-f", &missing, "frog"])),
+            Err(MinigrepError::FileNotFound(name)) if name == missing
+        ));
+    }
+
+    #[test]
+    fn search_any_matches_every_pattern() {
+        let patterns = vec!["frog".to_string(), "Bog".to_string()];
+        let contents = "a frog\na bog\na dog";
+
+        assert_eq!(vec!["a frog"], search_any(&patterns, contents, true));
+        assert_eq!(
+            vec!["a frog", "a bog"],
+            search_any(&patterns, contents, false)
+        );
+    }
+
+    #[test]
+    fn run_to_writer_writes_exact_bytes() {
+        let dir = env::temp_dir().join("minigrep_run_to_writer_writes_exact_bytes");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("windows.txt");
+        fs::write(&file, "Rust:\r\nsafe, fast\r\nTrust me.").unwrap();
+
+        let mut config = config("ust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        let mut output = Vec::new();
+        assert!(run_to_writer(&config, &mut output).unwrap());
+        assert_eq!(b"Rust:\nTrust me.\n".to_vec(), output);
+
+        config.quiet = true;
+        let mut output = Vec::new();
+        assert!(run_to_writer(&config, &mut output).unwrap());
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn field_restricts_matching() {
+        let dir = env::temp_dir().join("minigrep_field_restricts_matching");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("crabs.tsv");
+        fs::write(&file, "ferris\tRust\trust\nrusty\tGo\nghost\n").unwrap();
+
+        let mut config = Config::new(args(&[
+            "--field",
+            "2",
+            "--delimiter=\t",
+            "-i",
+            "-o",
+            "rust",
+        ]))
+        .unwrap();
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        assert_eq!(vec!["Rust"], run_collect(&config).unwrap());
+
+        config.only_matching = false;
+        config.field = Some(3);
+        assert_eq!(vec!["ferris\tRust\trust"], run_collect(&config).unwrap());
+
+        assert_eq!(Some(4..8), field_range(&config, "a\tb\tRust\t"));
+        assert_eq!(None, field_range(&config, "a\tb"));
+        assert_eq!(
+            "Fields are numbered from 1",
+            config_error(&["--field", "0", "rust"])
+        );
+    }
+
+    #[test]
+    fn search_field_skips_short_lines() {
+        let contents = "a,rust\nrust\nb,,rust";
+
+        assert_eq!(vec!["a,rust"], search_field("rust", contents, 2, ",", true));
+        assert_eq!(
+            vec!["b,,rust"],
+            search_field("rust", contents, 3, ",", true)
+        );
+        assert_eq!(
+            vec!["rust", "b,,rust"],
+            search_field("RUST", contents, 1, "a", false)
+        );
+    }
+
+    #[test]
+    fn vimgrep_prints_every_match() {
+        let dir = env::temp_dir().join("minigrep_vimgrep_prints_every_match");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "Rust:\nnothing\ncafé rust, trust\n").unwrap();
+        let name = file.display();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.vimgrep = true;
+        config.after_context = 1;
+        assert_eq!(
+            vec![
+                format!("{}:3:7:café rust, trust", name),
+                format!("{}:3:14:café rust, trust", name),
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        config.invert_match = true;
+        config.stream = true;
+        config.after_context = 0;
+        assert_eq!(
+            vec![
+                format!("{}:1:1:Rust:", name),
+                format!("{}:2:1:nothing", name)
+            ],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn validate_checks_without_searching() {
+        let dir = env::temp_dir().join("minigrep_validate_checks_without_searching");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "Rust:\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned(), "-".to_string()];
+        assert!(config.validate().is_ok());
+
+        config.filenames.push(dir.to_string_lossy().into_owned());
+        assert!(matches!(
+            config.validate(),
+            Err(MinigrepError::InvalidConfig(_))
+        ));
+        config.recursive = true;
+        assert!(config.validate().is_ok());
+
+        config.max_count = Some(0);
+        match config.validate() {
+            Err(MinigrepError::InvalidConfig(message)) => {
+                assert_eq!("--max-count must be at least 1", message)
+            }
+            _ => panic!("expected an invalid config"),
+        }
+
+        config.max_count = None;
+        config.files_with_matches = true;
+        config.count_only = true;
+        assert!(matches!(
+            config.validate(),
+            Err(MinigrepError::InvalidConfig(_))
+        ));
+
+        config.count_only = false;
+        config.regex = true;
+        config.query = "(".to_string();
+        assert!(matches!(config.validate(), Err(MinigrepError::Regex(_))));
+
+        config.query = String::new();
+        assert!(matches!(
+            config.validate(),
+            Err(MinigrepError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn conflicting_options_are_rejected() {
+        let cases: [(&[&str], &str); 12] = [
+            (
+                &["-c", "-o"],
+                "-c/--count can't be used with -o/--only-matching",
+            ),
+            (
+                &["-c", "-l"],
+                "-c/--count can't be used with -l/--files-with-matches",
+            ),
+            (
+                &["-L", "-c"],
+                "-c/--count can't be used with -L/--files-without-match",
+            ),
+            (
+                &["--vimgrep", "-c"],
+                "-c/--count can't be used with --vimgrep",
+            ),
+            (
+                &["-l", "-L"],
+                "-l/--files-with-matches can't be used with -L/--files-without-match",
+            ),
+            (
+                &["-l", "-n"],
+                "-l/--files-with-matches can't be used with -n/--line-number",
+            ),
+            (
+                &["-o", "-l"],
+                "-l/--files-with-matches can't be used with -o/--only-matching",
+            ),
+            (
+                &["-l", "--vimgrep"],
+                "-l/--files-with-matches can't be used with --vimgrep",
+            ),
+            (
+                &["-L", "-n"],
+                "-L/--files-without-match can't be used with -n/--line-number",
+            ),
+            (
+                &["-L", "-o"],
+                "-L/--files-without-match can't be used with -o/--only-matching",
+            ),
+            (
+                &["-L", "--vimgrep"],
+                "-L/--files-without-match can't be used with --vimgrep",
+            ),
+            (
+                &["-v", "-o"],
+                "-o/--only-matching can't be used with -v/--invert-match",
+            ),
+        ];
+
+        for (flags, message) in cases.iter() {
+            let mut flags = flags.to_vec();
+            flags.push("rust");
+            assert_eq!(*message, config_error(&flags));
+        }
+    }
+
+    #[test]
+    fn case_folding_beyond_lowercase() {
+        let contents = "Größe: groß\nGROSSE Liebe\nΣίσυφος\nnothing";
+
+        assert_eq!(
+            vec!["Größe: groß", "GROSSE Liebe"],
+            search_case_folded("gross", contents)
+        );
+        assert_eq!(
+            vec!["GROSSE Liebe"],
+            search_case_insensitive("gross", contents)
+        );
+        assert_eq!(vec!["Σίσυφος"], search_case_folded("ΣΊΣΥΦΟΣ", contents));
+        assert_eq!(vec!["Größe: groß"], search_case_folded("GRÖSSE", contents));
+    }
+
+    #[test]
+    fn line_range_limits_the_search() {
+        let dir = env::temp_dir().join("minigrep_line_range_limits_the_search");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("numbers.txt");
+        fs::write(&file, "rust 1\nrust 2\nrust 3\nrust 4\n").unwrap();
+
+        let mut config = Config::new(args(&["--line-range", "2:3", "-n", "rust"])).unwrap();
+        assert_eq!(Some((2, 3)), config.line_range);
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        assert_eq!(vec!["2:rust 2", "3:rust 3"], run_collect(&config).unwrap());
+
+        config.line_range = Some((4, 100));
+        assert_eq!(vec!["4:rust 4"], run_collect(&config).unwrap());
+
+        config.line_range = Some((10, 20));
+        assert!(run_collect(&config).unwrap().is_empty());
+
+        config.line_range = Some((3, 3));
+        config.after_context = 1;
+        assert_eq!(vec!["3:rust 3", "4-rust 4"], run_collect(&config).unwrap());
+
+        assert_eq!(
+            Some((1, usize::MAX)),
+            Config::new(args(&["--line-range=:", "rust"]))
+                .unwrap()
+                .line_range
+        );
+        assert_eq!(
+            "Lines are numbered from 1",
+            config_error(&["--line-range", "0:3", "rust"])
+        );
+        assert_eq!(
+            "The line range ends before it starts",
+            config_error(&["--line-range", "3:2", "rust"])
+        );
+        assert_eq!(
+            "Expected a line range like START:END",
+            config_error(&["--line-range", "3", "rust"])
+        );
+    }
+
+    #[test]
+    fn search_line_range_keeps_line_numbers() {
+        let contents = "rust\nrust\nrust";
+
+        assert_eq!(vec![(2, "rust")], search_line_range("rust", contents, 2, 2));
+        assert_eq!(
+            vec![(3, "rust")],
+            search_line_range("rust", contents, 3, usize::MAX)
+        );
+        assert!(search_line_range("rust", contents, 4, 9).is_empty());
+    }
+
+    #[test]
+    fn mmap_matches_read() {
+        let dir = env::temp_dir().join("minigrep_mmap_matches_read");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        let empty = dir.join("empty.txt");
+        fs::write(&file, "Rust:\r\nsafe, fast\nTrust me.").unwrap();
+        fs::write(&empty, "").unwrap();
+
+        let mut config = config("ust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.show_line_numbers = true;
+        let read = run_collect(&config).unwrap();
+
+        config.mmap = true;
+        assert_eq!(read, run_collect(&config).unwrap());
+        assert_eq!(vec!["1:Rust:", "3:Trust me."], read);
+
+        config.filenames = vec![empty.to_string_lossy().into_owned()];
+        assert!(run_collect(&config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn replace_prints_replaced_lines() {
+        let dir = env::temp_dir().join("minigrep_replace_prints_replaced_lines");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("dates.txt");
+        fs::write(&file, "from 2021-03 to 2022-11\nno dates\n").unwrap();
+
+        let mut config = Config::new(args(&["-e", "--replace", "$2/$1", r"(\d+)-(\d+)"])).unwrap();
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        assert_eq!(
+            vec!["from 03/2021 to 11/2022"],
+            run_collect(&config).unwrap()
+        );
+
+        config.regex = false;
+        config.case_sensitive = false;
+        config.query = "O".to_string();
+        config.replace = Some("0".to_string());
+        assert_eq!(
+            vec!["fr0m 2021-03 t0 2022-11", "n0 dates"],
+            run_collect(&config).unwrap()
+        );
+
+        assert_eq!("a-b-c", replace_matches("x", "-", "axbXc", false));
+        assert_eq!("a-bXc", replace_matches("x", "-", "axbXc", true));
+        assert_eq!("same", replace_matches("", "-", "same", true));
+    }
+
+    #[test]
+    fn in_place_rewrites_files() {
+        let dir = env::temp_dir().join("minigrep_in_place_rewrites_files");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "rust and rust\r\nkeep me\nrust").unwrap();
+        let name = file.to_string_lossy().into_owned();
+
+        let config =
+            Config::new(args(&["-s", "--replace=iron", "--in-place", "rust", &name])).unwrap();
+        assert!(run_collect(&config).unwrap().is_empty());
+        assert_eq!(
+            "iron and iron\r\nkeep me\niron",
+            fs::read_to_string(&file).unwrap()
+        );
+        assert!(!dir.join(".poem.txt.minigrep-tmp").exists());
+
+        assert_eq!(
+            "--in-place needs --replace",
+            config_error(&["--in-place", "rust", &name])
+        );
+        assert_eq!(
+            "--in-place can't rewrite standard input",
+            config_error(&["--replace", "x", "--in-place", "rust"])
+        );
+    }
+
+    #[test]
+    fn lines_scanned_stops_at_max_count() {
+        let mut config = config("rust");
+        assert_eq!(10, lines_scanned(&config, 10, &[2, 5]));
+
+        config.max_count = Some(2);
+        assert_eq!(6, lines_scanned(&config, 10, &[2, 5]));
+
+        config.line_range = Some((4, 20));
+        assert_eq!(7, lines_scanned(&config, 10, &[5]));
+        assert_eq!(0, lines_scanned(&config, 2, &[]));
+    }
+
+    #[test]
+    fn null_data_splits_on_nul() {
+        let dir = env::temp_dir().join("minigrep_null_data_splits_on_nul");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("records.bin");
+        fs::write(&file, "rust\nwith a newline\0nothing\0trust\r\0").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.null_data = true;
+        let mut output = Vec::new();
+        run_to_writer(&config, &mut output).unwrap();
+        assert_eq!(b"rust\nwith a newline\0trust\r\0".to_vec(), output);
+
+        config.stream = true;
+        config.byte_offset = true;
+        let mut output = Vec::new();
+        run_to_writer(&config, &mut output).unwrap();
+        assert_eq!(b"0:rust\nwith a newline\x0028:trust\r\0".to_vec(), output);
+    }
+
+    #[test]
+    fn null_ends_file_names() {
+        let dir = env::temp_dir().join("minigrep_null_ends_file_names");
+        fs::create_dir_all(&dir).unwrap();
+        let spaced = dir.join("with space.txt");
+        let other = dir.join("other.txt");
+        fs::write(&spaced, "rust\n").unwrap();
+        fs::write(&other, "duct\n").unwrap();
+        let files = vec![
+            spaced.to_string_lossy().into_owned(),
+            other.to_string_lossy().into_owned(),
+        ];
+
+        let mut config = config("rust");
+        config.filenames = files.clone();
+        config.null = true;
+        config.files_with_matches = true;
+        let mut output = Vec::new();
+        run_to_writer(&config, &mut output).unwrap();
+        assert_eq!(format!("{}\0", files[0]).into_bytes(), output);
+
+        config.files_with_matches = false;
+        let mut output = Vec::new();
+        run_to_writer(&config, &mut output).unwrap();
+        assert_eq!(format!("{}\0rust\n", files[0]).into_bytes(), output);
+    }
+
+    #[test]
+    fn anchored_options_match_one_end() {
+        let dir = env::temp_dir().join("minigrep_anchored_options_match_one_end");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(
+            &file,
+            "rust at the start\nat the end, rust\n rust after a space\nrust\n",
+        )
+        .unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.starts_with = true;
+        assert_eq!(
+            vec!["rust at the start", "rust"],
+            run_collect(&config).unwrap()
+        );
+
+        config.starts_with = false;
+        config.ends_with = true;
+        config.only_matching = true;
+        assert_eq!(vec!["rust", "rust"], run_collect(&config).unwrap());
+
+        config.only_matching = false;
+        config.starts_with = true;
+        config.regex = true;
+        config.query = "r.st".to_string();
+        assert_eq!(vec!["rust"], run_collect(&config).unwrap());
+    }
+
+    #[test]
+    fn anchored_searches_respect_case() {
+        let contents = "Rust first\nlast RUST\nmiddle rust here";
+
+        assert_eq!(
+            vec!["Rust first"],
+            search_starts_with("Rust", contents, true)
+        );
+        assert!(search_starts_with("rust", contents, true).is_empty());
+        assert_eq!(vec!["last RUST"], search_ends_with("rust", contents, false));
+        assert!(search_ends_with("rust", contents, true).is_empty());
+    }
+
+    #[test]
+    fn match_color_specs() {
+        assert_eq!(Some("01;32".to_string()), parse_match_color("ms=01;32"));
+        assert_eq!(
+            Some("4".to_string()),
+            parse_match_color("sl=:mt=1:ms=4:ln=32")
+        );
+        assert_eq!(Some("1".to_string()), parse_match_color("mt=1:fn=35:"));
+        assert_eq!(None, parse_match_color("ln=32"));
+        assert_eq!(None, parse_match_color("ms=red"));
+        assert_eq!(None, parse_match_color("ms="));
+        assert_eq!(None, parse_match_color("ms"));
+    }
+
+    #[test]
+    fn match_color_is_used_for_highlights() {
+        let dir = env::temp_dir().join("minigrep_match_color_is_used_for_highlights");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "Rust: trust\n").unwrap();
+
+        let mut config = config("ust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.color = ColorChoice::Always;
+        config.match_color = "04;36".to_string();
+        assert_eq!(
+            vec!["R\x1b[04;36must\x1b[0m: tr\x1b[04;36must\x1b[0m"],
+            run_collect(&config).unwrap()
+        );
+
+        config.only_matching = true;
+        assert_eq!(
+            vec!["\x1b[04;36must\x1b[0m", "\x1b[04;36must\x1b[0m"],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn group_prints_a_block_per_file() {
+        let dir = env::temp_dir().join("minigrep_group_prints_a_block_per_file");
+        fs::create_dir_all(&dir).unwrap();
+        let files: Vec<String> = [
+            ("a.txt", "rust\nsafe\n"),
+            ("b.txt", "none\n"),
+            ("c.txt", "x\ntrust\nrusty\n"),
+        ]
+        .iter()
+        .map(|(name, contents)| {
+            let path = dir.join(name);
+            fs::write(&path, contents).unwrap();
+            path.to_string_lossy().into_owned()
+        })
+        .collect();
+
+        let mut config = config("rust");
+        config.filenames = files.clone();
+        config.group = true;
+        config.show_line_numbers = true;
+        assert_eq!(
+            vec![
+                files[0].as_str(),
+                "1:rust",
+                "",
+                files[2].as_str(),
+                "2:trust",
+                "3:rusty"
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        assert_eq!(
+            "--group can't be used with -l/--files-with-matches",
+            config_error(&["--group", "-l", "rust"])
+        );
+    }
+
+    #[test]
+    fn filename_prefixes_can_be_forced() {
+        let dir = env::temp_dir().join("minigrep_filename_prefixes_can_be_forced");
+        fs::create_dir_all(&dir).unwrap();
+        let files: Vec<String> = ["a.txt", "b.txt"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                fs::write(&path, "rust\n").unwrap();
+                path.to_string_lossy().into_owned()
+            })
+            .collect();
+        let run = |filenames: &[String], with_filename| {
+            let mut config = config("rust");
+            config.filenames = filenames.to_vec();
+            config.with_filename = with_filename;
+            run_collect(&config).unwrap()
+        };
+
+        assert_eq!(
+            vec![format!("{}:rust", files[0])],
+            run(&files[..1], Some(true))
+        );
+        assert_eq!(vec!["rust"], run(&files[..1], Some(false)));
+        assert_eq!(
+            vec![format!("{}:rust", files[0]), format!("{}:rust", files[1])],
+            run(&files, Some(true))
+        );
+        assert_eq!(vec!["rust", "rust"], run(&files, Some(false)));
+
+        let parsed = Config::new(args(&["-H", "-h", "rust"])).unwrap();
+        assert_eq!(Some(false), parsed.with_filename);
+    }
+
+    #[test]
+    fn searches_time_out() {
+        let dir = env::temp_dir().join("minigrep_searches_time_out");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("large.txt");
+        let contents: String = (0..200_000).map(|i| format!("line {}\n", i)).collect();
+        fs::write(&file, contents).unwrap();
+
+        for stream in [false, true] {
+            let mut config = config("line 199999");
+            config.filenames = vec![file.to_string_lossy().into_owned()];
+            config.stream = stream;
+            config.timeout = Some(Duration::from_nanos(1));
+            assert!(matches!(
+                run_collect(&config),
+                Err(MinigrepError::Timeout(timeout)) if timeout == Duration::from_nanos(1)
+            ));
+
+            config.timeout = Some(Duration::from_secs(60));
+            assert_eq!(vec!["line 199999"], run_collect(&config).unwrap());
+        }
+
+        let parsed = Config::new(args(&["--timeout", "1.5", "rust"])).unwrap();
+        assert_eq!(Some(Duration::from_millis(1500)), parsed.timeout);
+        assert!(config_error(&["--timeout", "-1", "rust"]).contains("seconds"));
+    }
+
+    #[test]
+    fn gzipped_files_are_decompressed() {
+        use flate2::write::GzEncoder;
+
+        let dir = env::temp_dir().join("minigrep_gzipped_files_are_decompressed");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("log.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"ok\nrust error\nok\n").unwrap();
+        fs::write(&file, encoder.finish().unwrap()).unwrap();
+
+        let mut config = config("error");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.show_line_numbers = true;
+        assert_eq!(vec!["2:rust error"], run_collect(&config).unwrap());
+        config.stream = true;
+        assert_eq!(vec!["2:rust error"], run_collect(&config).unwrap());
+
+        let plain = dir.join("log.txt");
+        fs::write(&plain, "rust error\n").unwrap();
+        config.filenames = vec![plain.to_string_lossy().into_owned()];
+        config.decompress = true;
+        match run_collect(&config) {
+            Err(MinigrepError::Io(e)) => assert!(e.to_string().contains("not a valid gzip file")),
+            _ => panic!("expected a gzip error"),
+        }
+    }
+
+    #[test]
+    fn smart_case_minds_case_only_for_uppercase_queries() {
+        let dir = env::temp_dir().join("minigrep_smart_case_minds_case_only_for_uppercase_queries");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "Rust:\nTrust me.\n").unwrap();
+        let run = |query: &str| {
+            let mut config = config(query);
+            config.filenames = vec![file.to_string_lossy().into_owned()];
+            config.smart_case = true;
+            run_collect(&config).unwrap()
+        };
+
+        assert_eq!(vec!["Rust:", "Trust me."], run("rust"));
+        assert_eq!(vec!["Rust:"], run("Rust"));
+
+        // It wins over -s and -i, wherever they are.
+        let parsed = Config::new(args(&["--smart-case", "-s", "rust"])).unwrap();
+        assert!(!parsed.searches_case_sensitively());
+        let parsed = Config::new(args(&["-i", "--smart-case", "Rust"])).unwrap();
+        assert!(parsed.searches_case_sensitively());
+
+        // The patterns count as well as the query.
+        let parsed = Config::new(args(&["--smart-case", "--any", "rust,Trust"])).unwrap();
+        assert!(parsed.case_sensitive);
+        let patterns = dir.join("patterns.txt");
+        fs::write(&patterns, "rust\nTrust\n").unwrap();
+        let parsed =
+            Config::new(args(&["--smart-case", "-f", patterns.to_str().unwrap()])).unwrap();
+        assert!(parsed.case_sensitive);
+        let parsed = Config::new(args(&["--smart-case", "--any", "rust,trust"])).unwrap();
+        assert!(!parsed.case_sensitive);
+    }
+
+    #[test]
+    fn surrounding_whitespace_can_be_ignored() {
+        let dir = env::temp_dir().join("minigrep_surrounding_whitespace_can_be_ignored");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "   rust is safe  \nrusty\n").unwrap();
+
+        let mut config = config(" rust is safe   ");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        assert!(run_collect(&config).unwrap().is_empty());
+
+        config.ignore_whitespace = true;
+        assert_eq!(vec!["   rust is safe  "], run_collect(&config).unwrap());
+
+        config.query = "^rust is safe$".to_string();
+        config.regex = true;
+        assert_eq!(vec!["   rust is safe  "], run_collect(&config).unwrap());
+    }
+
+    #[test]
+    fn blank_lines_can_be_skipped() {
+        let dir = env::temp_dir().join("minigrep_blank_lines_can_be_skipped");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "rust\n\n  \t\nsafe\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.invert_match = true;
+        assert_eq!(vec!["", "  \t", "safe"], run_collect(&config).unwrap());
+
+        config.skip_blank = true;
+        assert_eq!(vec!["safe"], run_collect(&config).unwrap());
+        config.stream = true;
+        assert_eq!(vec!["safe"], run_collect(&config).unwrap());
+
+        config.count_only = true;
+        assert_eq!(vec!["1"], run_collect(&config).unwrap());
+
+        config.count_only = false;
+        config.stream = false;
+        config.invert_match = false;
+        config.after_context = 2;
+        assert_eq!(vec!["rust"], run_collect(&config).unwrap());
+    }
+
+    #[test]
+    fn search_with_config_minds_matching_options() {
+        let contents = "Rust:\nsafe, fast\n(trust\nproductive.";
+
+        let mut config = config("rust");
+        config.filenames = vec!["no_such_file.txt".to_string()];
+        config.recursive = true;
+        assert_eq!(vec!["(trust"], search_with_config(&config, contents));
+
+        config.case_sensitive = false;
+        config.invert_match = true;
+        assert_eq!(
+            vec!["safe, fast", "productive."],
+            search_with_config(&config, contents)
+        );
+
+        config.query = "(t".to_string();
+        config.case_sensitive = true;
+        config.invert_match = false;
+        config.regex = true;
+        assert_eq!(vec!["(trust"], search_with_config(&config, contents));
+
+        config.query = "^(?:safe|productive)".to_string();
+        config.field = Some(1);
+        assert_eq!(
+            vec!["safe, fast", "productive."],
+            search_with_config(&config, contents)
+        );
+    }
+
+    #[test]
+    fn shell_quoting() {
+        assert_eq!("''", shell_quote(""));
+        assert_eq!("'rust is safe'", shell_quote("rust is safe"));
+        assert_eq!(r"'don'\''t '\'''\'''", shell_quote("don't ''"));
+        assert_eq!("'one\ntwo $HOME `ls`'", shell_quote("one\ntwo $HOME `ls`"));
+    }
+
+    #[test]
+    fn shell_escape_quotes_printed_matches() {
+        let dir = env::temp_dir().join("minigrep_shell_escape_quotes_printed_matches");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "rust's $safe\nfast\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.shell_escape = true;
+        config.show_line_numbers = true;
+        assert_eq!(vec![r"1:'rust'\''s $safe'"], run_collect(&config).unwrap());
+
+        config.only_matching = true;
+        assert_eq!(vec!["1:'rust'"], run_collect(&config).unwrap());
+    }
+
+    #[test]
+    fn unique_keeps_the_first_of_each_line() {
+        let dir = env::temp_dir().join("minigrep_unique_keeps_the_first_of_each_line");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("log.txt");
+        fs::write(
+            &file,
+            "rust error\nok\nrust warning\nrust error\n  rust error \nrust warning\n",
+        )
+        .unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.unique = true;
+        for stream in [false, true] {
+            config.stream = stream;
+            assert_eq!(
+                vec!["rust error", "rust warning", "  rust error "],
+                run_collect(&config).unwrap()
+            );
+        }
+
+        config.ignore_whitespace = true;
+        assert_eq!(
+            vec!["rust error", "rust warning"],
+            run_collect(&config).unwrap()
+        );
+        config.count_only = true;
+        assert_eq!(vec!["2"], run_collect(&config).unwrap());
+    }
+
+    #[test]
+    fn exclude_pattern_leaves_lines_out() {
+        let contents = "error: disk full\nerror: Timeout\nok\n";
+
+        assert_eq!(
+            vec!["error: disk full", "error: Timeout"],
+            search_excluding("error", "timeout", contents, true)
+        );
+        assert_eq!(
+            vec!["error: disk full"],
+            search_excluding("error", "timeout", contents, false)
+        );
+
+        let mut config = config("error");
+        config.exclude_pattern = Some("timeout".to_string());
+        config.case_sensitive = false;
+        assert_eq!(
+            vec!["error: disk full"],
+            search_with_config(&config, contents)
+        );
+
+        config.regex = true;
+        config.exclude_pattern = Some("t[io]+me".to_string());
+        assert_eq!(
+            vec!["error: disk full"],
+            search_with_config(&config, contents)
+        );
+
+        config.invert_match = true;
+        assert_eq!(
+            vec!["error: Timeout", "ok"],
+            search_with_config(&config, contents)
+        );
+
+        let parsed = Config::new(args(&["--exclude-pattern", "timeout", "error"])).unwrap();
+        assert_eq!(Some("timeout".to_string()), parsed.exclude_pattern);
+    }
+
+    #[test]
+    fn config_displays_a_field_per_line() {
+        let config = Config::new(args(&[
+            "-n",
+            "--max-count",
+            "2",
+            "-s",
+            "a \"b\"\t",
+            "poem.txt",
+        ]))
+        .unwrap();
+        let shown = config.to_string();
+        let lines: Vec<&str> = shown.lines().collect();
+
+        for line in [
+            r#"query: "a \"b\"\t""#,
+            r#"filenames: ["poem.txt"]"#,
+            "case_sensitive: true",
+            "show_line_numbers: true",
+            "max_count: Some(2)",
+            "format: Text",
+            "debug_config: false",
+        ] {
+            assert!(lines.contains(&line), "{:?} isn't shown", line);
+        }
+        assert_eq!(shown, config.to_string());
+    }
+
+    #[test]
+    fn paragraphs_are_matched_whole() {
+        let contents =
+            "Rust:\nsafe, fast.\n\nPick three:\nyou can trust it,\nproductive.\n  \nDuct tape.\n";
+        assert_eq!(
+            vec!["Pick three:\nyou can trust it,\nproductive."],
+            search_paragraphs("TRUST", contents, false)
+        );
+        assert!(search_paragraphs("TRUST", contents, true).is_empty());
+
+        let dir = env::temp_dir().join("minigrep_paragraphs_are_matched_whole");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, contents).unwrap();
+
+        let mut config = config("u");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.paragraph = true;
+        config.show_line_numbers = true;
+        assert_eq!(
+            vec![
+                "1:Rust:",
+                "2-safe, fast.",
+                "",
+                "4-Pick three:",
+                "5:you can trust it,",
+                "6:productive.",
+                "",
+                "8:Duct tape."
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        config.query = "trust".to_string();
+        config.count_only = true;
+        assert_eq!(vec!["1"], run_collect(&config).unwrap());
+    }
+
+    #[test]
+    fn max_depth_stops_recursion() {
+        let dir = env::temp_dir().join("minigrep_max_depth_stops_recursion");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("node_modules/deep")).unwrap();
+        fs::write(dir.join("top.txt"), "rust\n").unwrap();
+        fs::write(dir.join("node_modules/one.txt"), "rust\n").unwrap();
+        fs::write(dir.join("node_modules/deep/two.txt"), "rust\n").unwrap();
+
+        let mut config = Config::new(args(&["-r", "--max-depth", "1", "rust"])).unwrap();
+        config.filenames = vec![dir.to_string_lossy().into_owned()];
+        config.color = ColorChoice::Never;
+        assert_eq!(
+            vec![
+                format!("{}:rust", dir.join("node_modules/one.txt").display()),
+                format!("{}:rust", dir.join("top.txt").display()),
+            ],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn include_globs_limit_recursion() {
+        let dir = env::temp_dir().join("minigrep_include_globs_limit_recursion");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("notes.txt"), "rust\n").unwrap();
+        fs::write(dir.join("src/main.rs"), "rust\n").unwrap();
+        fs::write(dir.join("src/lib.rs"), "rust\n").unwrap();
+
+        let mut config = Config::new(args(&["-r", "-h", "--include", "*.rs", "rust"])).unwrap();
+        config.filenames = vec![dir.to_string_lossy().into_owned()];
+        config.color = ColorChoice::Never;
+        assert_eq!(vec!["rust", "rust"], run_collect(&config).unwrap());
+
+        config.with_filename = None;
+        config.exclude = vec!["main.*".to_string()];
+        assert_eq!(
+            vec![format!("{}:rust", dir.join("src/lib.rs").display())],
+            run_collect(&config).unwrap()
+        );
+
+        assert!(config_error(&["--exclude", "[", "rust"]).starts_with("Invalid glob ["));
+    }
+
+    #[test]
+    fn match_ranges_of_occurrences() {
+        assert!(match_ranges("rust", "safe, fast", true).is_empty());
+        assert_eq!(vec![2..6], match_ranges("rust", "a rust b", true));
+        assert_eq!(vec![0..2, 2..4], match_ranges("aa", "aaaaa", true));
+        assert_eq!(
+            vec![0..4, 7..11],
+            match_ranges("RUST", "Rust, trust", false)
+        );
+        // 'İ' is two bytes but lowercases to three.
+        assert_eq!(vec![0..2, 3..5], match_ranges("i̇", "İ İ", false));
+        assert!(match_ranges("", "rust", true).is_empty());
+    }
+
+    #[test]
+    fn hidden_files_are_only_searched_with_hidden() {
+        let dir = env::temp_dir().join("minigrep_hidden_files_are_only_searched_with_hidden");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(".hidden"), "rust\n").unwrap();
+        fs::write(dir.join("shown.txt"), "rust\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![dir.to_string_lossy().into_owned()];
+        config.recursive = true;
+        assert_eq!(
+            vec![format!("{}:rust", dir.join("shown.txt").display())],
+            run_collect(&config).unwrap()
+        );
+
+        config.hidden = true;
+        assert_eq!(
+            vec![
+                format!("{}:rust", dir.join(".hidden").display()),
+                format!("{}:rust", dir.join("shown.txt").display()),
+            ],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn search_bytes_needs_no_utf8() {
+        let contents: &[u8] = b"\x00\x9f\x92rust\xff\r\nnone\n\xc3\x28 BIN\xde\xad\n\n";
+
+        assert_eq!(
+            vec![&b"\x00\x9f\x92rust\xff"[..]],
+            search_bytes(b"rust", contents)
+        );
+        assert_eq!(
+            vec![&b"\xc3\x28 BIN\xde\xad"[..]],
+            search_bytes(b"\xde\xad", contents)
+        );
+        assert_eq!(4, search_bytes(b"", contents).len());
+        assert!(search_bytes(b"\xde\xad", b"\xde\n\xad").is_empty());
+        assert!(search_bytes(b"", b"").is_empty());
+    }
+
+    #[test]
+    fn block_mode_indents_each_match_with_its_context() {
+        let dir = env::temp_dir().join("minigrep_block_mode_indents_each_match_with_its_context");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "Rust:\nsafe, fast,\nproductive.\nPick three.\n").unwrap();
+        let name = file.to_string_lossy().into_owned();
+
+        let mut config = config("fast");
+        config.filenames = vec![name.clone()];
+        config.block = true;
+        config.before_context = 1;
+        config.after_context = 1;
+        assert_eq!(
+            vec![
+                format!("{}:1", name),
+                "    Rust:".to_string(),
+                "    safe, fast,".to_string(),
+                "    productive.".to_string(),
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        config.query = "Rust|three".to_string();
+        config.regex = true;
+        config.before_context = 0;
+        config.after_context = 0;
+        config.show_line_numbers = true;
+        assert_eq!(
+            vec![
+                format!("{}:1", name),
+                "    1:Rust:".to_string(),
+                String::new(),
+                format!("{}:4", name),
+                "    4:Pick three.".to_string(),
+            ],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn line_specs() {
+        assert_eq!(vec![3], parse_line_spec("3").unwrap());
+        assert_eq!(
+            vec![3, 7, 15, 16, 17, 18, 19, 20],
+            parse_line_spec("3,7,15-20").unwrap()
+        );
+        assert_eq!(vec![1, 2, 3, 5], parse_line_spec("5, 2-3,1-2 ,3").unwrap());
+        assert_eq!(vec![4], parse_line_spec("4-4").unwrap());
+
+        // Huge spans are kept as ranges, and too long to list.
+        assert_eq!(
+            vec![(1, 3), (5, 5), (7, 99_999_999_999)],
+            parse_line_ranges("5, 2-3,1-2 ,3,7-99999999999").unwrap()
+        );
+
+        let error = |spec| match parse_line_spec(spec) {
+            Err(MinigrepError::InvalidConfig(message)) => message,
+            _ => panic!("expected {} to be invalid", spec),
+        };
+        assert_eq!("Expected line numbers like 3,7,15-20, not ", error(""));
+        assert_eq!(
+            "Expected line numbers like 3,7,15-20, not 3,,7",
+            error("3,,7")
+        );
+        assert!(error("three").starts_with("Expected line numbers"));
+        assert!(error("1-").starts_with("Expected line numbers"));
+        assert!(error("-2").starts_with("Expected line numbers"));
+        assert!(error("1-2-3").starts_with("Expected line numbers"));
+        assert_eq!("Lines are numbered from 1", error("0,2"));
+        assert_eq!("The line range ends before it starts", error("9-2"));
+        assert_eq!(
+            "The line list is too long to list every line",
+            error("1-99999999999")
+        );
+    }
+
+    #[test]
+    fn line_lists_limit_the_search() {
+        let dir = env::temp_dir().join("minigrep_line_lists_limit_the_search");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "rust 1\nrust 2\nrust 3\nrust 4\nrust 5\n").unwrap();
+
+        let mut config = Config::new(args(&["-n", "--lines", "2,4-9", "rust"])).unwrap();
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.color = ColorChoice::Never;
+        assert_eq!(
+            vec!["2:rust 2", "4:rust 4", "5:rust 5"],
+            run_collect(&config).unwrap()
+        );
+
+        config.line_range = Some((3, 4));
+        assert_eq!(vec!["4:rust 4"], run_collect(&config).unwrap());
+        config.invert_match = true;
+        assert!(run_collect(&config).unwrap().is_empty());
+
+        assert!(config_error(&["--lines", "2,x", "rust"]).starts_with("Expected line numbers"));
+
+        config.line_range = None;
+        config.invert_match = false;
+        config.lines = Some(vec![(0, 1)]);
+        assert_eq!(vec!["1:rust 1"], run_collect(&config).unwrap());
+        config.lines = Some(vec![(0, 0)]);
+        assert!(run_collect(&config).unwrap().is_empty());
+        assert!(config_error(&["--lines", "0-2", "rust"]).starts_with("Lines are numbered"));
+    }
+
+    #[test]
+    fn span_reports_first_and_last_matches() {
+        let dir = env::temp_dir().join("minigrep_span_reports_first_and_last_matches");
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.txt");
+        fs::write(&first, "ok\nrust\nok\nrust\nrust\nok\n").unwrap();
+        let second = dir.join("second.txt");
+        fs::write(&second, "ok\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![first.to_string_lossy().into_owned()];
+        config.span = true;
+        for stream in [false, true] {
+            config.stream = stream;
+            assert_eq!(
+                vec!["first=2 last=5 count=3"],
+                run_collect(&config).unwrap()
+            );
+        }
+
+        config.filenames.push(second.to_string_lossy().into_owned());
+        assert_eq!(
+            vec![
+                format!("{}:first=2 last=5 count=3", first.display()),
+                format!("{}:no matches", second.display()),
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        config.filenames.remove(0);
+        let mut output = Vec::new();
+        assert!(!run_to_writer(&config, &mut output).unwrap());
+        assert_eq!(b"no matches\n".to_vec(), output);
+
+        assert_eq!(
+            "--span can't be used with --format json",
+            config_error(&["--span", "--format", "json", "rust"])
+        );
+    }
+
+    #[test]
+    fn case_sensitivity_follows_its_precedence() {
+        // Nothing set: case matters.
+        assert!(resolve_case_sensitivity(None, false, "rust", false));
+        // The environment variable alone makes it not matter.
+        assert!(!resolve_case_sensitivity(None, false, "rust", true));
+        // The command line wins over the environment variable.
+        assert!(resolve_case_sensitivity(Some(true), false, "rust", true));
+        assert!(!resolve_case_sensitivity(Some(false), false, "rust", false));
+        // Smart case wins over everything, going by the query.
+        assert!(!resolve_case_sensitivity(Some(true), true, "rust", false));
+        assert!(resolve_case_sensitivity(Some(false), true, "Rust", true));
+        assert!(resolve_case_sensitivity(None, true, "Rust", false));
+        assert!(!resolve_case_sensitivity(None, true, "rust", true));
+    }
+
+    #[test]
+    fn leading_timestamps_are_read_as_far_as_given() {
+        assert_eq!(
+            Some((vec![2024, 5, 1], " up")),
+            leading_timestamp("2024-05-01 up")
+        );
+        assert_eq!(
+            Some((vec![2024, 5, 1, 12, 30], " up")),
+            leading_timestamp("2024-05-01T12:30 up")
+        );
+        assert_eq!(
+            Some((vec![2024, 5, 1, 12, 30, 5, 250_000_000], "Z up")),
+            leading_timestamp("2024-05-01 12:30:05.25Z up")
+        );
+        assert_eq!(None, leading_timestamp("2024-13-01 up"));
+        assert_eq!(None, leading_timestamp("[2024-05-01] up"));
+        assert_eq!(None, leading_timestamp("up"));
+
+        assert_eq!(
+            Some(vec![2024, 5, 1, 12, 30]),
+            parse_timestamp("2024-05-01T12:30Z")
+        );
+        assert_eq!(None, parse_timestamp("2024-05-01 up"));
+    }
+
+    #[test]
+    fn log_lines_are_searched_within_the_time_window() {
+        let dir = env::temp_dir().join("minigrep_log_lines_are_searched_within_the_time_window");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("app.log");
+        fs::write(
+            &file,
+            "2024-05-01T23:59:59 ERROR disk full\n\
+             2024-05-02T00:00:00 ERROR disk full\n\
+             2024-05-02T08:15:00 INFO disk checked\n\
+             \tat disk.rs:10 (ERROR)\n\
+             2024-05-02T23:59:59.999 ERROR disk gone\n\
+             2024-05-03T00:00:00 ERROR disk back\n",
+        )
+        .unwrap();
+        let run = |since: Option<&str>, until: Option<&str>, keep_untimed: bool| {
+            let mut config = config("ERROR");
+            config.filenames = vec![file.to_string_lossy().into_owned()];
+            config.since = since.map(String::from);
+            config.until = until.map(String::from);
+            config.keep_untimed = keep_untimed;
+            run_collect(&config).unwrap()
+        };
+
+        assert_eq!(
+            vec![
+                "2024-05-02T00:00:00 ERROR disk full",
+                "2024-05-02T23:59:59.999 ERROR disk gone",
+            ],
+            run(Some("2024-05-02"), Some("2024-05-02"), false)
+        );
+        assert_eq!(
+            vec![
+                "2024-05-02T00:00:00 ERROR disk full",
+                "\tat disk.rs:10 (ERROR)",
+                "2024-05-02T23:59:59.999 ERROR disk gone",
+            ],
+            run(Some("2024-05-02"), Some("2024-05-02"), true)
+        );
+        assert_eq!(
+            vec![
+                "2024-05-01T23:59:59 ERROR disk full",
+                "2024-05-02T00:00:00 ERROR disk full",
+            ],
+            run(None, Some("2024-05-02T00:00"), false)
+        );
+        assert_eq!(
+            vec!["2024-05-03T00:00:00 ERROR disk back"],
+            run(Some("2024-05-03T00:00:00Z"), None, false)
+        );
+
+        let parsed = Config::new(args(&[
+            "--since",
+            "2024-05-02",
+            "--until=2024-05-02",
+            "--keep-untimed",
+            "ERROR",
+        ]))
+        .unwrap();
+        assert_eq!(Some("2024-05-02".to_string()), parsed.since);
+        assert_eq!(Some("2024-05-02".to_string()), parsed.until);
+        assert!(parsed.keep_untimed);
+        assert!(Config::new(args(&["--since", "yesterday", "ERROR"])).is_err());
+    }
+
+    #[test]
+    fn matches_are_counted_per_file() {
+        let dir = env::temp_dir().join("minigrep_matches_are_counted_per_file");
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.txt");
+        let second = dir.join("second.txt");
+        fs::write(&first, "Rust\nrust\ntrust\nsafe\n").unwrap();
+        fs::write(&second, "safe\nfast\n").unwrap();
+        let mut config = config("rust");
+        config.filenames = vec![
+            first.to_string_lossy().into_owned(),
+            second.to_string_lossy().into_owned(),
+        ];
+        let counts = |config: &Config| {
+            count_matches_per_file(config)
+                .unwrap()
+                .into_iter()
+                .map(|(_, count)| count)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            vec![
+                (config.filenames[0].clone(), 2),
+                (config.filenames[1].clone(), 0),
+            ],
+            count_matches_per_file(&config).unwrap()
+        );
+
+        config.case_sensitive = false;
+        assert_eq!(vec![3, 0], counts(&config));
+
+        config.invert_match = true;
+        assert_eq!(vec![1, 2], counts(&config));
+
+        config
+            .filenames
+            .push(dir.join("missing.txt").to_string_lossy().into_owned());
+        assert!(count_matches_per_file(&config).is_err());
+    }
+
+    #[test]
+    fn line_delimiter_splits_and_joins_records() {
+        let dir = env::temp_dir().join("minigrep_line_delimiter_splits_and_joins_records");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("records.txt");
+        fs::write(&file, "rust;safe\nand fast;trust;go;").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.line_delimiter = Some(';');
+        let mut output = Vec::new();
+        run_to_writer(&config, &mut output).unwrap();
+        assert_eq!(b"rust;trust;".to_vec(), output);
+
+        config.query = "fast".to_string();
+        config.stream = true;
+        config.show_line_numbers = true;
+        assert_eq!(vec!["2:safe\nand fast"], run_collect(&config).unwrap());
+
+        let parsed = Config::new(args(&["--line-delimiter", ";", "rust"])).unwrap();
+        assert_eq!(Some(';'), parsed.line_delimiter);
+        assert!(Config::new(args(&["--line-delimiter", ";;", "rust"])).is_err());
+        assert!(Config::new(args(&["--line-delimiter=é", "rust"])).is_err());
+        assert!(Config::new(args(&["--line-delimiter=;", "-z", "rust"])).is_err());
+    }
+
+    #[test]
+    fn occurrences_are_counted_without_overlaps() {
+        assert_eq!(0, count_occurrences_in_line("rust", "safe and fast", true));
+        assert_eq!(1, count_occurrences_in_line("rust", "rust is safe", true));
+        assert_eq!(
+            3,
+            count_occurrences_in_line("rust", "rust, trust, rusty", true)
+        );
+        assert_eq!(2, count_occurrences_in_line("aa", "aaaa", true));
+        assert_eq!(1, count_occurrences_in_line("aa", "aaa", true));
+        assert_eq!(0, count_occurrences_in_line("RUST", "rust", true));
+        assert_eq!(2, count_occurrences_in_line("RUST", "rust Rust", false));
+    }
+
+    #[test]
+    fn occurrences_are_printed_per_matching_line() {
+        let dir = env::temp_dir().join("minigrep_occurrences_are_printed_per_matching_line");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "safe\nrust\nrust, trust\naaaa\n").unwrap();
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.count_per_line = true;
+        assert_eq!(
+            vec!["2:1:rust", "3:2:rust, trust"],
+            run_collect(&config).unwrap()
+        );
+
+        config.query = "aa".to_string();
+        config.stream = true;
+        assert_eq!(vec!["4:2:aaaa"], run_collect(&config).unwrap());
+
+        assert!(
+            Config::new(args(&["--only-count-per-line", "rust"]))
+                .unwrap()
+                .count_per_line
+        );
+        assert!(Config::new(args(&["--only-count-per-line", "-v", "rust"])).is_err());
+    }
+
+    #[test]
+    fn only_unreadable_walked_files_are_skipped() {
+        let input = |walked: bool| Input {
+            name: "poem.txt".to_string(),
+            walked: walked.then(|| "poem.txt".to_string()),
+        };
+        let error = |kind| io::Error::from(kind);
+
+        assert!(is_skippable(
+            &input(true),
+            &error(io::ErrorKind::PermissionDenied)
+        ));
+        assert!(is_skippable(&input(true), &error(io::ErrorKind::NotFound)));
+        assert!(!is_skippable(
+            &input(false),
+            &error(io::ErrorKind::PermissionDenied)
+        ));
+        assert!(!is_skippable(
+            &input(true),
+            &error(io::ErrorKind::InvalidData)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_files_are_skipped_in_recursive_search() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join("minigrep_unreadable_files_are_skipped_in_recursive_search");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let locked = dir.join("locked.txt");
+        fs::write(dir.join("open.txt"), "rust\n").unwrap();
+        fs::write(&locked, "rust\n").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // Permissions don't stop a privileged user, who can read anything.
+        if fs::File::open(&locked).is_ok() {
+            return;
+        }
+
+        let mut config = config("rust");
+        config.filenames = vec![dir.to_string_lossy().into_owned()];
+        config.recursive = true;
+        config.stats = true;
+        let result = run_collect(&config);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let results = result.unwrap();
+        assert_eq!(
+            format!("{}:rust", dir.join("open.txt").display()),
+            results[0]
+        );
+        assert_eq!("1 match in 1 file (1 file searched, 1 skipped)", results[1]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn broken_symlinks_are_skipped_in_recursive_search() {
+        let dir = env::temp_dir().join("minigrep_broken_symlinks_are_skipped_in_recursive_search");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("open.txt"), "rust\n").unwrap();
+        std::os::unix::fs::symlink(dir.join("missing.txt"), dir.join("broken.txt")).unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![dir.to_string_lossy().into_owned()];
+        config.recursive = true;
+        config.stats = true;
+        assert_eq!(
+            vec![
+                format!("{}:rust", dir.join("open.txt").display()),
+                "1 match in 1 file (1 file searched, 1 skipped)".to_string(),
+            ],
+            run_collect(&config).unwrap()
+        );
+        assert_eq!(1, execute(&config).unwrap().files_skipped);
+    }
+
+    #[test]
+    fn matches_carry_their_metadata() {
+        let contents = "Rust\r\nsafe\ntrust me\nrust";
+        let mut config = config("rust");
+
+        let found: Vec<Match> = Matches::new(&config, contents).collect();
+        assert_eq!(
+            vec![
+                Match {
+                    file: None,
+                    line_number: 3,
+                    text: "trust me".to_string(),
+                    byte_offset: 11,
+                },
+                Match {
+                    file: None,
+                    line_number: 4,
+                    text: "rust".to_string(),
+                    byte_offset: 20,
+                },
+            ],
+            found
+        );
+
+        config.case_sensitive = false;
+        let found: Vec<Match> = Matches::new(&config, contents)
+            .with_file("poem.txt")
+            .collect();
+        assert_eq!(3, found.len());
+        assert_eq!(
+            Match {
+                file: Some("poem.txt".to_string()),
+                line_number: 1,
+                text: "Rust".to_string(),
+                byte_offset: 0,
+            },
+            found[0]
+        );
+
+        config.invert_match = true;
+        let mut matches = Matches::new(&config, contents);
+        assert_eq!((1, Some(1)), matches.size_hint());
+        let found = matches.next().unwrap();
+        assert_eq!(
+            (2, 6, "safe"),
+            (found.line_number, found.byte_offset, found.text.as_str())
+        );
+        assert_eq!(None, matches.next());
+    }
+
+    #[test]
+    fn any_matches_lines_with_one_of_the_terms() {
+        let dir = env::temp_dir().join("minigrep_any_matches_lines_with_one_of_the_terms");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("pets.txt");
+        fs::write(&file, "a Dog barks\nbirds sing\nfish.*swim\n").unwrap();
+        let file = file.to_string_lossy().into_owned();
+
+        let config = Config::new(args(&["--any", "cat,dog,fish.*", "-i", &file])).unwrap();
+        assert_eq!(vec![file.clone()], config.filenames);
+        assert_eq!(
+            vec!["a Dog barks", "fish.*swim"],
+            run_collect(&config).unwrap()
+        );
+
+        let config = Config::new(args(&["-s", "--any=cat,dog,,", &file])).unwrap();
+        assert!(run_collect(&config).unwrap().is_empty());
+
+        assert!(Config::new(args(&["--any", ",", &file])).is_err());
+    }
+
+    #[test]
+    fn all_matches_lines_with_every_term() {
+        let terms = ["bar".to_string(), "foo".to_string()];
+        let contents = "foo only\nbar then foo\nFOO and BAR\nneither";
+        assert_eq!(vec!["bar then foo"], search_all(&terms, contents, true));
+        assert_eq!(
+            vec!["bar then foo", "FOO and BAR"],
+            search_all(&terms, contents, false)
+        );
+
+        let dir = env::temp_dir().join("minigrep_all_matches_lines_with_every_term");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("notes.txt");
+        fs::write(&file, contents).unwrap();
+        let file = file.to_string_lossy().into_owned();
+
+        let config = Config::new(args(&["-s", "--all", "foo,bar", &file])).unwrap();
+        assert!(config.all_terms);
+        assert_eq!(vec!["bar then foo"], run_collect(&config).unwrap());
+        let config = Config::new(args(&["-i", "-r", "--all=foo,bar", &file])).unwrap();
+        assert_eq!(
+            vec!["bar then foo", "FOO and BAR"],
+            run_collect(&config).unwrap()
+        );
+        assert!(Config::new(args(&["--all", "foo", "--fuzzy", "1", &file])).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follow_searches_symlinked_directories() {
+        let base = env::temp_dir().join("minigrep_follow_searches_symlinked_directories");
+        let _ = fs::remove_dir_all(&base);
+        let (root, elsewhere) = (base.join("root"), base.join("elsewhere"));
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&elsewhere).unwrap();
+        fs::write(elsewhere.join("poem.txt"), "rust\n").unwrap();
+        std::os::unix::fs::symlink(&elsewhere, root.join("link")).unwrap();
+        let root = root.to_string_lossy().into_owned();
+
+        let config = Config::new(args(&["-r", "rust", &root])).unwrap();
+        assert!(run_collect(&config).unwrap().is_empty());
+
+        let config = Config::new(args(&["-r", "--follow", "rust", &root])).unwrap();
+        assert_eq!(
+            vec![format!(
+                "{}:rust",
+                Path::new(&root).join("link/poem.txt").display()
+            )],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn absolute_path_prefixes_matches_with_absolute_paths() {
+        let dir =
+            env::temp_dir().join("minigrep_absolute_path_prefixes_matches_with_absolute_paths");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/poem.txt"), "rust\n").unwrap();
+        let relative = dir.join("sub/../sub");
+        let relative = relative.to_string_lossy().into_owned();
+
+        let config = Config::new(args(&["-r", "--absolute-path", "rust", &relative])).unwrap();
+        let results = run_collect(&config).unwrap();
+        let expected = fs::canonicalize(dir.join("sub/poem.txt")).unwrap();
+        assert_eq!(vec![format!("{}:rust", expected.display())], results);
+        assert!(Path::new(results[0].trim_end_matches(":rust")).is_absolute());
+
+        assert_eq!("missing.txt", absolute_name("missing.txt"));
+        assert_eq!(STDIN_FILENAME, absolute_name(STDIN_FILENAME));
+    }
+
+    #[test]
+    fn match_filename_reports_files_by_name() {
+        let dir = env::temp_dir().join("minigrep_match_filename_reports_files_by_name");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Rust-notes.txt"), "nothing here\n").unwrap();
+        fs::write(dir.join("other.txt"), "rust inside\n").unwrap();
+        let root = dir.to_string_lossy().into_owned();
+        let name = dir.join("Rust-notes.txt").display().to_string();
+        let other = dir.join("other.txt").display().to_string();
+
+        let config = Config::new(args(&["-r", "-s", "--match-filename", "rust", &root])).unwrap();
+        assert_eq!(
+            vec![format!("{}:rust inside", other)],
+            run_collect(&config).unwrap()
+        );
+
+        let mut config =
+            Config::new(args(&["-r", "-i", "--match-filename", "rust", &root])).unwrap();
+        config.stats = true;
+        assert_eq!(
+            vec![
+                format!("{}{}", name, FILENAME_MATCH_MARKER),
+                format!("{}:rust inside", other),
+                "2 matches in 2 files (2 files searched)".to_string(),
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        config.match_filename = false;
+        config.stats = false;
+        assert_eq!(
+            vec![format!("{}:rust inside", other)],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn match_filename_ignores_the_walked_directory() {
+        let dir = env::temp_dir()
+            .join("minigrep_match_filename_ignores_the_walked_directory")
+            .join("rust-project");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("notes.txt"), "nothing here\n").unwrap();
+        fs::write(dir.join("src").join("rust.txt"), "nothing here\n").unwrap();
+        let root = dir.to_string_lossy().into_owned();
+        let name = dir.join("src").join("rust.txt").display().to_string();
+
+        let mut config = Config::new(args(&["-r", "--match-filename", "rust", &root])).unwrap();
+        assert_eq!(
+            vec![format!("{}{}", name, FILENAME_MATCH_MARKER)],
+            run_collect(&config).unwrap()
+        );
+
+        config.color = ColorChoice::Always;
+        assert_eq!(
+            vec![format!(
+                "{}\x1b[01;31mrust\x1b[0m.txt{}",
+                dir.join("src").join("").display(),
+                FILENAME_MATCH_MARKER
+            )],
+            run_collect(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn tabs_expand_to_the_next_stop() {
+        assert_eq!("    rust", expand_tabs("\trust", 4));
+        assert_eq!("        rust", expand_tabs("\t\trust", 4));
+        assert_eq!("ab  cd", expand_tabs("ab\tcd", 4));
+        assert_eq!("abcd    e", expand_tabs("abcd\te", 4));
+        assert_eq!("a       b", expand_tabs("a\t\tb", 4));
+        assert_eq!("ab", expand_tabs("a\tb", 0));
+        assert_eq!("no tabs", expand_tabs("no tabs", 8));
+        assert_eq!(
+            "\x1b[01;31mab\x1b[0m  c",
+            expand_tabs("\x1b[01;31mab\x1b[0m\tc", 4)
+        );
+    }
+
+    #[test]
+    fn tabs_are_expanded_only_in_printed_lines() {
+        let dir = env::temp_dir().join("minigrep_tabs_are_expanded_only_in_printed_lines");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("table.tsv");
+        fs::write(&file, "id\tname\n1\trust\n").unwrap();
+        let mut config = config("\trust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.byte_offset = true;
+        config.before_context = 1;
+        assert_eq!(
+            vec!["0-id\tname", "8:1\trust"],
+            run_collect(&config).unwrap()
+        );
+
+        config.tabs = Some(4);
+        assert_eq!(
+            vec!["0-id  name", "8:1   rust"],
+            run_collect(&config).unwrap()
+        );
+        assert_eq!(
+            Some(8),
+            Config::new(args(&["--tabs", "8", "rust"])).unwrap().tabs
+        );
+    }
+
+    #[test]
+    fn last_line_is_searched_without_a_trailing_newline() {
+        let dir = env::temp_dir().join("minigrep_last_line_is_searched_without_a_trailing_newline");
+        fs::create_dir_all(&dir).unwrap();
+        let ended = dir.join("ended.log");
+        let unended = dir.join("unended.log");
+        fs::write(&ended, "start\nrust\nend rust\n").unwrap();
+        fs::write(&unended, "start\nrust\nend rust").unwrap();
+
+        for file in [&ended, &unended] {
+            let mut config = config("rust");
+            config.filenames = vec![file.to_string_lossy().into_owned()];
+            config.show_line_numbers = true;
+            let expected = vec!["2:rust", "3:end rust"];
+            assert_eq!(expected, run_collect(&config).unwrap(), "{:?}", file);
+
+            config.stream = true;
+            assert_eq!(expected, run_collect(&config).unwrap(), "{:?}", file);
+            config.stream = false;
+            config.mmap = true;
+            assert_eq!(expected, run_collect(&config).unwrap(), "{:?}", file);
+            config.mmap = false;
+
+            config.query = "start".to_string();
+            config.after_context = 5;
+            assert_eq!(
+                vec!["1:start", "2-rust", "3-end rust"],
+                run_collect(&config).unwrap(),
+                "{:?}",
+                file
+            );
+        }
+
+        let contents = "rust\r\nend rust";
+        assert_eq!(vec!["rust", "end rust"], search("rust", contents));
+        assert_eq!(
+            vec!["rust", "end rust"],
+            search("rust", "rust\r\nend rust\r")
+        );
+        let found: Vec<Match> = Matches::new(&config("end"), contents).collect();
+        assert_eq!((2, 6), (found[0].line_number, found[0].byte_offset));
+    }
+
+    #[test]
+    fn last_record_is_searched_without_a_trailing_delimiter() {
+        let dir =
+            env::temp_dir().join("minigrep_last_record_is_searched_without_a_trailing_delimiter");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("records.txt");
+        fs::write(&file, "rust;safe;trust").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.line_delimiter = Some(';');
+        assert_eq!(vec!["rust", "trust"], run_collect(&config).unwrap());
+        config.stream = true;
+        assert_eq!(vec!["rust", "trust"], run_collect(&config).unwrap());
+    }
+
+    #[test]
+    fn in_place_keeps_a_missing_trailing_newline_missing() {
+        let dir =
+            env::temp_dir().join("minigrep_in_place_keeps_a_missing_trailing_newline_missing");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "rust\nend rust").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.replace = Some("go".to_string());
+        config.in_place = true;
+        run_collect(&config).unwrap();
+        assert_eq!("go\nend go", fs::read_to_string(&file).unwrap());
+    }
+
+    #[test]
+    fn matches_are_printed_under_their_headings() {
+        let dir = env::temp_dir().join("minigrep_matches_are_printed_under_their_headings");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("guide.md");
+        fs::write(
+            &file,
+            "rust intro\n\
+             # Install\n\
+             get rust\n\
+             then rust up\n\
+             # Use\n\
+             nothing here\n\
+             ## Rust tips\n\
+             rust fmt\n",
+        )
+        .unwrap();
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.heading_pattern = Some("^#".to_string());
+        let expected = vec![
+            "rust intro",
+            "2=# Install",
+            "get rust",
+            "then rust up",
+            "7=## Rust tips",
+            "rust fmt",
+        ];
+        assert_eq!(expected, run_collect(&config).unwrap());
+
+        config.stream = true;
+        assert_eq!(expected, run_collect(&config).unwrap());
+
+        config.stream = false;
+        config.show_line_numbers = true;
+        config.before_context = 1;
+        config.after_context = 1;
+        assert_eq!(
+            vec![
+                "1:rust intro",
+                "2-# Install",
+                "3:get rust",
+                "4:then rust up",
+                "5-# Use",
+                "--",
+                "7-## Rust tips",
+                "8:rust fmt",
+            ],
+            run_collect(&config).unwrap()
+        );
+        fs::write(&file, "# one\ntwo\nrust\n").unwrap();
+        assert_eq!(
+            vec!["1=# one", "2-two", "3:rust"],
+            run_collect(&config).unwrap()
+        );
+        config.before_context = 0;
+        config.after_context = 0;
+
+        config.heading_pattern = Some("(".to_string());
+        assert!(matches!(run_collect(&config), Err(MinigrepError::Regex(_))));
+        assert_eq!(
+            Some("^fn ".to_string()),
+            Config::new(args(&["--heading-pattern", "^fn ", "rust"]))
+                .unwrap()
+                .heading_pattern
+        );
+    }
+
+    #[test]
+    fn matching_lines_can_be_sorted() {
+        let dir = env::temp_dir().join("minigrep_matching_lines_can_be_sorted");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(
+            &file,
+            "rust\nrust, rust, rust\nsafe\nrusty and trusty\nrust it\n",
+        )
+        .unwrap();
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.show_line_numbers = true;
+
+        config.sort = SortOrder::Length;
+        assert_eq!(
+            vec![
+                "2:rust, rust, rust",
+                "4:rusty and trusty",
+                "5:rust it",
+                "1:rust"
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        config.sort = SortOrder::Matches;
+        config.stream = true;
+        assert_eq!(
+            vec![
+                "2:rust, rust, rust",
+                "4:rusty and trusty",
+                "1:rust",
+                "5:rust it"
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        config.sort = SortOrder::None;
+        assert_eq!(
+            vec![
+                "1:rust",
+                "2:rust, rust, rust",
+                "4:rusty and trusty",
+                "5:rust it"
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        let parse =
+            |order: &str| Config::new(args(&["--sort", order, "rust"])).map(|config| config.sort);
+        assert_eq!(SortOrder::Length, parse("length").unwrap());
+        assert_eq!(SortOrder::Matches, parse("matches").unwrap());
+        assert_eq!(SortOrder::None, parse("none").unwrap());
+        assert!(parse("size").is_err());
+        assert!(Config::new(args(&["--sort", "length", "-C", "1", "rust"])).is_err());
+    }
+
+    #[test]
+    fn transient_read_failures_are_retried() {
+        /// A reader that fails the way a file being rotated might.
+        struct Busy;
+        impl Read for Busy {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("resource busy"))
+            }
+        }
+
+        let mut attempts = 0;
+        let contents = retry(2, || {
+            attempts += 1;
+            let reader: Box<dyn Read> = if attempts == 1 {
+                Box::new(Busy)
+            } else {
+                Box::new(io::Cursor::new("rust\n"))
+            };
+            read_contents(reader, false)
+        });
+        assert_eq!("rust\n", contents.unwrap());
+        assert_eq!(2, attempts);
+
+        let mut attempts = 0;
+        let result = retry(3, || {
+            attempts += 1;
+            read_contents(Busy, false)
+        });
+        assert!(result.is_err());
+        assert_eq!(4, attempts);
+
+        let mut attempts = 0;
+        let result = retry(3, || {
+            attempts += 1;
+            fs::read_to_string("file_that_does_not_exist.txt")
+        });
+        assert_eq!(io::ErrorKind::NotFound, result.unwrap_err().kind());
+        assert_eq!(1, attempts);
+
+        let mut config = config("rust");
+        config.retries = Some(3);
+        let mut attempts = 0;
+        let result: io::Result<()> = with_retries(&config, STDIN_FILENAME, || {
+            attempts += 1;
+            Err(io::Error::other("resource busy"))
+        });
+        assert!(result.is_err());
+        assert_eq!(1, attempts);
+        assert_eq!(
+            Some(2),
+            Config::new(args(&["--retries", "2", "rust"]))
+                .unwrap()
+                .retries
+        );
+    }
+
+    #[test]
+    fn search_reader_calls_back_for_each_match() {
+        let reader = io::Cursor::new("Rust\nsafe\r\ntrust me\nrust");
+        let mut calls = Vec::new();
+        let count = search_reader(reader, "rust", true, |number, line| {
+            calls.push((number, line.to_string()))
+        })
+        .unwrap();
+        assert_eq!(2, count);
+        assert_eq!(
+            vec![(3, "trust me".to_string()), (4, "rust".to_string())],
+            calls
+        );
+
+        let reader = io::Cursor::new("Rust\nsafe\n");
+        let mut numbers = Vec::new();
+        assert_eq!(
+            1,
+            search_reader(reader, "RUST", false, |number, _| numbers.push(number)).unwrap()
+        );
+        assert_eq!(vec![1], numbers);
+
+        let reader = io::Cursor::new(&b"rust\n\xff\n"[..]);
+        let mut calls = 0;
+        let error = search_reader(reader, "rust", true, |_, _| calls += 1).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert_eq!(1, calls);
+    }
+
+    #[test]
+    fn escape_sequences_are_stripped() {
+        assert_eq!("plain", strip_ansi("plain"));
+        assert_eq!("red text", strip_ansi("\x1b[31mred\x1b[0m text"));
+        assert_eq!("bold", strip_ansi("\x1b[1m\x1b[38;5;208mbold\x1b[m"));
+        assert_eq!("up", strip_ansi("\x1b[2Aup\x1b[K"));
+        assert_eq!(
+            "link",
+            strip_ansi("\x1b]8;;https://example.com\x07link\x1b]8;;\x1b\\")
+        );
+        assert_eq!("saved", strip_ansi("\x1b7saved"));
+        assert_eq!("cut ", strip_ansi("cut \x1b[38;5"));
+        assert_eq!("title \nerror\n", strip_ansi("title \x1b]0;x\nerror\n"));
+        assert_eq!("a \nerror\n", strip_ansi("a \x1b[\nerror\n"));
+        assert_eq!("ünï", strip_ansi("\x1b[32mü\x1b[0mnï"));
+    }
+
+    #[test]
+    fn strip_ansi_matches_the_visible_text() {
+        let dir = env::temp_dir().join("minigrep_strip_ansi_matches_the_visible_text");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("colored.log");
+        fs::write(&file, "\x1b[31mdisk\x1b[0m full\nall fine\n").unwrap();
+        let mut config = config("disk full");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        assert!(run_collect(&config).unwrap().is_empty());
+
+        config.strip_ansi = true;
+        assert_eq!(vec!["disk full"], run_collect(&config).unwrap());
+        config.stream = true;
+        assert_eq!(vec!["disk full"], run_collect(&config).unwrap());
+
+        config.print_original = true;
+        assert_eq!(
+            vec!["\x1b[31mdisk\x1b[0m full"],
+            run_collect(&config).unwrap()
+        );
+        config.stream = false;
+        assert_eq!(
+            vec!["\x1b[31mdisk\x1b[0m full"],
+            run_collect(&config).unwrap()
+        );
+
+        // Only the visible text is highlighted or printed on its own.
+        config.color = ColorChoice::Always;
+        assert_eq!(
+            vec!["\x1b[31m\x1b[01;31mdisk\x1b[0m full\x1b[0m"],
+            run_collect(&config).unwrap()
+        );
+        config.query = "full".to_string();
+        config.color = ColorChoice::Never;
+        config.only_matching = true;
+        assert_eq!(vec!["full"], run_collect(&config).unwrap());
+        config.query = "disk full".to_string();
+        assert_eq!(vec!["disk\x1b[0m full"], run_collect(&config).unwrap());
+
+        let parsed = Config::new(args(&["--strip-ansi", "--print-original", "disk"])).unwrap();
+        assert!(parsed.strip_ansi && parsed.print_original);
+    }
+
+    #[test]
+    fn unterminated_escapes_end_with_their_line() {
+        let dir = env::temp_dir().join("minigrep_unterminated_escapes_end_with_their_line");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("cut.log");
+        let mut config = config("error");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.strip_ansi = true;
+
+        for contents in [
+            "title \x1b]0;x\nerror one\nerror two\n",
+            "a \x1b[\nerror one\nerror two\n",
+        ] {
+            fs::write(&file, contents).unwrap();
+            config.stream = false;
+            assert_eq!(
+                vec!["error one", "error two"],
+                run_collect(&config).unwrap()
+            );
+            config.stream = true;
+            assert_eq!(
+                vec!["error one", "error two"],
+                run_collect(&config).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn join_prints_the_matches_on_one_line() {
+        let dir = env::temp_dir().join("minigrep_join_prints_the_matches_on_one_line");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "rust\nsafe\ntrust\nrust\nrusty\nrusted\n").unwrap();
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.join = Some(", ".to_string());
+        assert_eq!(
+            vec!["rust, trust, rust, rusty, rusted"],
+            run_collect(&config).unwrap()
+        );
+
+        config.unique = true;
+        config.max_count = Some(3);
+        config.stats = true;
+        assert_eq!(
+            vec![
+                "rust, trust, rusty",
+                "3 matches in 1 file (1 file searched)"
+            ],
+            run_collect(&config).unwrap()
+        );
+
+        config.query = "go".to_string();
+        config.stats = false;
+        assert!(run_collect(&config).unwrap().is_empty());
+        assert_eq!(
+            Some(" ".to_string()),
+            Config::new(args(&["--join", " ", "rust"])).unwrap().join
+        );
+    }
+
+    /// Whether every color code in the text is reset before the end of
+    /// its line and before the next color code.
+    fn resets_every_highlight(text: &str) -> bool {
+        text.split('\n').all(|line| {
+            line.split("\x1b[01;31m").skip(1).all(|colored| {
+                colored
+                    .find(HIGHLIGHT_END)
+                    .is_some_and(|end| !colored[..end].contains('\x1b'))
+            })
+        })
+    }
+
+    #[test]
+    fn every_highlight_is_reset() {
+        let colored = |line: &str, query: &str| highlight_matches(line, query, false);
+        for line in [
+            colored("rust", "rust"),
+            colored("Rust, trust and rusty", "rust"),
+            colored("rustrust", "rust"),
+            colored("nothing", "rust"),
+        ] {
+            assert!(resets_every_highlight(&line), "{:?}", line);
+        }
+        let mut spanning = String::new();
+        push_colored(&mut spanning, "rust\ntrust", "01;31");
+        assert_eq!("\x1b[01;31mrust\x1b[0m\n\x1b[01;31mtrust\x1b[0m", spanning);
+
+        let dir = env::temp_dir().join("minigrep_every_highlight_is_reset");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "rust and\ntrust it\nRust\n").unwrap();
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.color = ColorChoice::Always;
+        config.case_sensitive = false;
+        let mut output = Vec::new();
+        run_to_writer(&config, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(3, output.matches(HIGHLIGHT_END).count());
+        assert!(resets_every_highlight(&output), "{:?}", output);
+
+        config.query = "and\\ntr".to_string();
+        config.regex = true;
+        config.multiline = true;
+        config.only_matching = true;
+        let mut output = Vec::new();
+        run_to_writer(&config, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!("\x1b[01;31mand\x1b[0m\n\x1b[01;31mtr\x1b[0m\n", output);
+        assert!(resets_every_highlight(&output), "{:?}", output);
+    }
+
+    #[test]
+    fn encoded_files_are_decoded_before_searching() {
+        let dir = env::temp_dir().join("minigrep_encoded_files_are_decoded_before_searching");
+        fs::create_dir_all(&dir).unwrap();
+        let latin1 = dir.join("latin1.txt");
+        // "café crème\nthé\n" in Latin-1.
+        fs::write(&latin1, b"caf\xe9 cr\xe8me\nth\xe9\n").unwrap();
+        let mut config = config("crème");
+        config.filenames = vec![latin1.to_string_lossy().into_owned()];
+        assert!(run_collect(&config).is_ok_and(|lines| lines.is_empty()));
+
+        config.encoding = Some("latin1".to_string());
+        assert_eq!(vec!["café crème"], run_collect(&config).unwrap());
+        config.stream = true;
+        config.query = "é".to_string();
+        assert_eq!(vec!["café crème", "thé"], run_collect(&config).unwrap());
+
+        let utf16 = dir.join("utf16.txt");
+        let mut bytes = vec![0xff, 0xfe];
+        bytes.extend("crème\n".encode_utf16().flat_map(u16::to_le_bytes));
+        fs::write(&utf16, bytes).unwrap();
+        // The byte order mark wins over the encoding.
+        config.filenames = vec![utf16.to_string_lossy().into_owned()];
+        config.query = "crème".to_string();
+        assert_eq!(vec!["crème"], run_collect(&config).unwrap());
+
+        let invalid = dir.join("invalid.txt");
+        fs::write(&invalid, b"\xff\xfeb\x00\x00\xd8").unwrap();
+        config.filenames = vec![invalid.to_string_lossy().into_owned()];
+        config.query = "b".to_string();
+        config.encoding = Some("utf-16le".to_string());
+        assert_eq!(vec!["b\u{fffd}"], run_collect(&config).unwrap());
+
+        let error = |args: &[&str]| match Config::new(self::args(args)) {
+            Err(MinigrepError::InvalidConfig(message)) => message,
+            _ => panic!("expected an invalid config"),
+        };
+        assert_eq!(
+            "Unknown encoding klingon",
+            error(&["--encoding", "klingon", "é"])
+        );
+    }
+
+    #[test]
+    fn regexes_are_validated_with_readable_errors() {
+        assert_eq!(Ok(()), validate_regex(r"^\d{4}-\d{2}$"));
+
+        let unbalanced = validate_regex("[a-z").unwrap_err();
+        assert!(
+            unbalanced.contains("unclosed character class"),
+            "{}",
+            unbalanced
+        );
+        assert!(unbalanced.contains("[a-z"), "{}", unbalanced);
+
+        let quantifier = validate_regex("a{2,1}").unwrap_err();
+        assert!(quantifier.contains("invalid"), "{}", quantifier);
+        let repeated = validate_regex("*rust").unwrap_err();
+        assert!(
+            repeated.contains("repetition operator missing expression"),
+            "{}",
+            repeated
+        );
+    }
+
+    #[test]
+    fn files0_from_reads_nul_separated_names() {
+        let dir = env::temp_dir().join("minigrep_files0_from_reads_nul_separated_names");
+        fs::create_dir_all(&dir).unwrap();
+        let poem = dir.join("poem.txt");
+        fs::write(&poem, "rust:\nsafe, fast.\n").unwrap();
+        let notes = dir.join("my notes.txt");
+        fs::write(&notes, "no\nrust here\n").unwrap();
+        let poem = poem.to_string_lossy().into_owned();
+        let notes = notes.to_string_lossy().into_owned();
+        let missing = dir.join("missing.txt").to_string_lossy().into_owned();
+        let list = dir.join("list");
+        fs::write(&list, format!("{}\0\0{}\0{}\0", poem, missing, notes)).unwrap();
+        let list = list.to_string_lossy().into_owned();
+
+        let config = Config::new(args(&["--files0-from", &list, "rust"])).unwrap();
+        assert_eq!(vec![poem.clone(), missing, notes.clone()], config.filenames);
+
+        let mut results = Vec::new();
+        assert!(search_files(&config, &mut results).is_err());
+        assert_eq!(
+            vec![format!("{}:rust:", poem), format!("{}:rust here", notes)],
+            results
+        );
+
+        fs::write(&list, "\0").unwrap();
+        let config = Config::new(args(&["--files0-from", &list, "rust"])).unwrap();
+        assert!(config.filenames.is_empty());
+        assert!(!run_to_writer(&config, &mut io::sink()).unwrap());
+
+        assert!(matches!(
+            Config::new(args(&["--files0-from", "no_such_list", "rust"])),
+            Err(MinigrepError::FileNotFound(_))
+        ));
+        assert_eq!(
+            "Didn't get a file list",
+            config_error(&["rust", "--files0-from"])
+        );
+    }
+
+    #[test]
+    fn relative_to_prefixes_matches_with_paths_from_the_base() {
+        let dir =
+            env::temp_dir().join("minigrep_relative_to_prefixes_matches_with_paths_from_the_base");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("base")).unwrap();
+        fs::create_dir_all(dir.join("src/sub")).unwrap();
+        fs::write(dir.join("src/poem.txt"), "rust\n").unwrap();
+        fs::write(dir.join("src/sub/notes.txt"), "rust too\n").unwrap();
+        let base = dir.join("base").to_string_lossy().into_owned();
+        let src = dir.join("src").to_string_lossy().into_owned();
+
+        let config = Config::new(args(&["-r", "--relative-to", &base, "rust", &src])).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            vec!["../src/poem.txt:rust", "../src/sub/notes.txt:rust too"],
+            run_collect(&config).unwrap()
+        );
+
+        let config = Config::new(args(&["-r", "--relative-to", &src, "rust", &src])).unwrap();
+        assert_eq!(
+            vec!["poem.txt:rust", "sub/notes.txt:rust too"],
+            run_collect(&config).unwrap()
+        );
+
+        assert_eq!(
+            Some(PathBuf::from(".")),
+            relative_path(Path::new("/tmp/sub"), Path::new("/tmp/sub"))
+        );
+        assert_eq!(None, relative_path(Path::new("sub"), Path::new("/tmp")));
+        assert_eq!(
+            STDIN_FILENAME,
+            relative_name(STDIN_FILENAME, Path::new("/tmp"))
+        );
+        assert_eq!(
+            "--relative-to can't be used with --absolute-path",
+            config_error(&["--relative-to", &base, "--absolute-path", "rust"])
+        );
+    }
+
+    #[test]
+    fn execute_reports_every_file_and_the_totals() {
+        let dir = env::temp_dir().join("minigrep_execute_reports_every_file_and_the_totals");
+        fs::create_dir_all(&dir).unwrap();
+        let mut filenames = Vec::new();
+        for (name, contents) in [
+            ("two.txt", "rust\nno\nrust again\n"),
+            ("none.txt", "nothing\nhere\n"),
+            ("three.txt", "rust\nrust\nrust\n"),
+        ] {
+            let file = dir.join(name);
+            fs::write(&file, contents).unwrap();
+            filenames.push(file.to_string_lossy().into_owned());
+        }
+
+        let mut config = config("rust");
+        config.filenames = filenames.clone();
+        config.show_line_numbers = true;
+        let report = execute(&config).unwrap();
+
+        assert_eq!(3, report.files_searched);
+        assert_eq!(2, report.files_with_matches);
+        assert_eq!(5, report.total_matches);
+        assert_eq!(8, report.lines_scanned);
+        assert_eq!(0, report.files_skipped);
+        let counts: Vec<(&str, usize)> = report
+            .files
+            .iter()
+            .map(|file| (file.name.as_str(), file.match_count))
+            .collect();
+        assert_eq!(
+            vec![
+                (filenames[0].as_str(), 2),
+                (filenames[1].as_str(), 0),
+                (filenames[2].as_str(), 3)
+            ],
+            counts
+        );
+        assert_eq!(
+            vec![
+                format!("{}:1:rust", filenames[0]),
+                format!("{}:3:rust again", filenames[0])
+            ],
+            report.files[0].lines
+        );
+        assert!(report.files[1].lines.is_empty());
+        assert_eq!(
+            "5 matches in 2 files (3 files searched)",
+            report.to_string()
+        );
+
+        config.stats = true;
+        assert_eq!(
+            Some(&report.to_string()),
+            run_collect(&config).unwrap().last()
+        );
+
+        config.stats = false;
+        config.quiet = true;
+        // Quiet mode stops at the first file with a match.
+        let report = execute(&config).unwrap();
+        assert_eq!(1, report.files_with_matches);
+        assert!(report.files.iter().all(|file| file.lines.is_empty()));
+        config.query = "zzz".to_string();
+        let report = execute(&config).unwrap();
+        assert_eq!(0, report.files_with_matches);
+        assert!(report.files.iter().all(|file| file.lines.is_empty()));
+        config.quiet = false;
+        config.query = "rust".to_string();
+
+        config
+            .filenames
+            .push(dir.join("missing.txt").to_string_lossy().into_owned());
+        assert!(matches!(
+            execute(&config),
+            Err(MinigrepError::FileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn auto_color_is_off_for_other_writers() {
+        let dir = env::temp_dir().join("minigrep_auto_color_is_off_for_other_writers");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "rust\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.color = ColorChoice::Auto;
+        let mut output = Vec::new();
+        run_to_writer(&config, &mut output).unwrap();
+        assert_eq!(b"rust\n".to_vec(), output);
+
+        config.color = ColorChoice::Always;
+        let mut output = Vec::new();
+        run_to_writer(&config, &mut output).unwrap();
+        assert!(output.starts_with(b"\x1b["));
+    }
+
+    #[test]
+    fn stats_leave_json_output_valid() {
+        let dir = env::temp_dir().join("minigrep_stats_leave_json_output_valid");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "rust\nrust again\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.format = OutputFormat::Json;
+        config.stats = true;
+        let results = run_collect(&config).unwrap();
+
+        assert_eq!(1, results.len());
+        let json: serde_json::Value = serde_json::from_str(&results[0]).unwrap();
+        assert_eq!(2, json.as_array().unwrap().len());
+    }
+
+    #[test]
+    fn multiline_rejects_fixed_strings() {
+        assert_eq!(
+            "-U/--multiline can't be used with -F/--fixed-strings",
+            config_error(&["-U", "-e", "-F", "a\\nb", "poem.txt"])
+        );
+    }
+
+    #[test]
+    fn both_anchors_match_the_whole_line() {
+        let dir = env::temp_dir().join("minigrep_both_anchors_match_the_whole_line");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("poem.txt");
+        fs::write(&file, "rust\nrust and rust\nrusty rust\n").unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![file.to_string_lossy().into_owned()];
+        config.starts_with = true;
+        config.ends_with = true;
+        for regex in [false, true] {
+            config.regex = regex;
+            config.only_matching = false;
+            config.color = ColorChoice::Never;
+            assert_eq!(vec!["rust"], run_collect(&config).unwrap());
+
+            config.only_matching = true;
+            assert_eq!(vec!["rust"], run_collect(&config).unwrap());
+
+            config.only_matching = false;
+            config.color = ColorChoice::Always;
+            assert_eq!(
+                vec!["\x1b[01;31mrust\x1b[0m"],
+                run_collect(&config).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn partial_prints_the_lines_found_before_a_timeout() {
+        let dir = env::temp_dir().join("minigrep_partial_prints_the_lines_found_before_a_timeout");
+        fs::create_dir_all(&dir).unwrap();
+        let small = dir.join("small.txt");
+        fs::write(&small, "rust\n").unwrap();
+        // Fuzzy matching is slow enough that this takes seconds.
+        let large = dir.join("large.txt");
+        let contents: String = (0..300_000)
+            .map(|i| format!("line {} of filler text\n", i))
+            .collect();
+        fs::write(&large, contents).unwrap();
+
+        let mut config = config("rust");
+        config.filenames = vec![
+            small.to_string_lossy().into_owned(),
+            large.to_string_lossy().into_owned(),
+        ];
+        config.fuzzy = Some(1);
+        config.threads = Some(1);
+        config.timeout = Some(Duration::from_millis(200));
+
+        let mut output = Vec::new();
+        assert!(matches!(
+            run_to_writer(&config, &mut output),
+            Err(MinigrepError::Timeout(_))
+        ));
+        assert!(output.is_empty());
+
+        config.partial = true;
+        let mut output = Vec::new();
+        assert!(matches!(
+            run_to_writer(&config, &mut output),
+            Err(MinigrepError::Timeout(_))
+        ));
+        assert_eq!(
+            format!("{}:rust\n", config.filenames[0]).into_bytes(),
+            output
+        );
+
+        assert!(Config::new(args(&["--partial", "rust"])).unwrap().partial);
+    }
+}
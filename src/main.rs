@@ -3,14 +3,36 @@ use std::process;
 
 use minigrep::Config;
 
+/// Exits like grep does: 0 when a line matched, 1 when none did, and 2
+/// when the arguments were invalid or a file couldn't be searched. With
+/// `--check` nothing is searched, and it exits 0 when the search could
+/// run or 2 when it couldn't. With `--debug-config` the parsed config is
+/// printed instead of searching.
 fn main() {
     let config = Config::new(env::args()).unwrap_or_else(|err| {
         eprintln!("Problem parsing arguments: {}", err);
-        process::exit(1);
+        process::exit(2);
     });
 
-    if let Err(e) = minigrep::run(config) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
-    };
+    if config.debug_config {
+        println!("{}", config);
+        return;
+    }
+
+    if config.check {
+        if let Err(e) = config.validate() {
+            eprintln!("Problem checking arguments: {}", e);
+            process::exit(2);
+        }
+        return;
+    }
+
+    match minigrep::run(config) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("Application error: {}", e);
+            process::exit(2);
+        }
+    }
 }
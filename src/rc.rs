@@ -0,0 +1,81 @@
+//! Defaults read from a `.minigreprc` file.
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::{ColorChoice, MinigrepError};
+
+/// The name of the file in the current directory that defaults are read
+/// from.
+pub const RC_FILENAME: &str = ".minigreprc";
+
+/// Values that are used when the command line doesn't give them.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Defaults {
+    pub(crate) query: Option<String>,
+    pub(crate) case_sensitive: Option<bool>,
+    pub(crate) color: Option<ColorChoice>,
+    pub(crate) context: Option<usize>,
+}
+
+impl Defaults {
+    /// Reads the defaults from the file at `path`, which is allowed to be
+    /// missing.
+    pub(crate) fn load(path: &Path) -> Result<Defaults, MinigrepError> {
+        match fs::read_to_string(path) {
+            Ok(text) => Defaults::parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Defaults::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub(crate) fn parse(text: &str) -> Result<Defaults, MinigrepError> {
+        toml::from_str(text)
+            .map_err(|e| MinigrepError::InvalidConfig(format!("Malformed {}: {}", RC_FILENAME, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_value() {
+        let defaults = Defaults::parse(
+            "query = \"TODO\"\ncase_sensitive = false\ncolor = \"always\"\ncontext = 2\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Defaults {
+                query: Some("TODO".to_string()),
+                case_sensitive: Some(false),
+                color: Some(ColorChoice::Always),
+                context: Some(2),
+            },
+            defaults
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        for text in &["context = \"two\"", "colour = \"always\"", "query = "] {
+            match Defaults::parse(text) {
+                Err(MinigrepError::InvalidConfig(message)) => {
+                    assert!(message.starts_with("Malformed .minigreprc"))
+                }
+                _ => panic!("expected {:?} to be rejected", text),
+            }
+        }
+    }
+
+    #[test]
+    fn missing_file_gives_no_defaults() {
+        let path = Path::new("file_that_does_not_exists.toml");
+
+        assert_eq!(Defaults::default(), Defaults::load(path).unwrap());
+    }
+}
@@ -0,0 +1,330 @@
+//! Walking directory trees for recursive search.
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use glob::Pattern;
+
+/// What limits a walk.
+#[derive(Default)]
+pub(crate) struct WalkOptions {
+    /// How many directories deep the walk goes below the root, where 0
+    /// only collects the files directly in the root.
+    pub(crate) max_depth: Option<usize>,
+    /// When there are any, only files matching one of these are collected.
+    pub(crate) include: Vec<Pattern>,
+    /// Files and directories matching any of these are skipped.
+    pub(crate) exclude: Vec<Pattern>,
+    /// Whether files and directories whose names start with a `.` are
+    /// walked, rather than skipped.
+    pub(crate) hidden: bool,
+    /// Whether symlinks to directories are walked into, rather than
+    /// skipped.
+    pub(crate) follow: bool,
+}
+
+impl WalkOptions {
+    /// Whether the file or directory found at `path` is skipped, going by
+    /// whether a glob matches its name or its path relative to `root`.
+    fn excludes(&self, root: &Path, path: &Path) -> bool {
+        self.exclude.iter().any(|glob| matches(glob, root, path))
+    }
+
+    /// Whether the file at `path` is collected.
+    fn includes(&self, root: &Path, path: &Path) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|glob| matches(glob, root, path)))
+            && !self.excludes(root, path)
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+fn matches(glob: &Pattern, root: &Path, path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| glob.matches(&name.to_string_lossy()))
+        || path
+            .strip_prefix(root)
+            .is_ok_and(|relative| glob.matches_path(relative))
+}
+
+/// Collects every regular file underneath `root` into `files`, visiting
+/// the entries of each directory in sorted order so the output is
+/// deterministic. Entries that can't be read are pushed onto `errors`
+/// and the walk carries on.
+///
+/// Symlinks to directories are skipped unless the options say to follow
+/// them. Even then every directory is entered at most once, going by its
+/// canonical path, so a symlink loop can't keep the walk going forever.
+/// Symlinks to files are always collected like the files themselves.
+///
+/// Directories nested deeper than the `max_depth` of the options below
+/// `root` aren't entered, and neither are ones matching an exclude glob.
+/// Files are only collected when the include and exclude globs let them
+/// be. Hidden files and directories, whose names start with a `.`, are
+/// skipped unless the options say otherwise, though `root` is walked
+/// whatever its name.
+pub(crate) fn walk(
+    root: &Path,
+    options: &WalkOptions,
+    files: &mut Vec<PathBuf>,
+    errors: &mut Vec<(PathBuf, io::Error)>,
+) {
+    let mut visited = HashSet::new();
+    visit(root, root, 0, options, &mut visited, files, errors);
+}
+
+fn visit(
+    root: &Path,
+    dir: &Path,
+    depth: usize,
+    options: &WalkOptions,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<PathBuf>,
+    errors: &mut Vec<(PathBuf, io::Error)>,
+) {
+    match fs::canonicalize(dir) {
+        Ok(canonical) => {
+            if !visited.insert(canonical) {
+                return;
+            }
+        }
+        Err(e) => return errors.push((dir.to_path_buf(), e)),
+    }
+
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| match entry {
+                Ok(entry) => Some(entry.path()),
+                Err(e) => {
+                    errors.push((dir.to_path_buf(), e));
+                    None
+                }
+            })
+            .collect(),
+        Err(e) => return errors.push((dir.to_path_buf(), e)),
+    };
+    entries.sort();
+    if !options.hidden {
+        entries.retain(|path| !is_hidden(path));
+    }
+
+    for path in entries {
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => {
+                if options.max_depth.is_none_or(|max_depth| depth < max_depth)
+                    && (options.follow || !path.is_symlink())
+                    && !options.excludes(root, &path)
+                {
+                    visit(root, &path, depth + 1, options, visited, files, errors);
+                }
+            }
+            Ok(metadata) if metadata.is_file() => {
+                if options.includes(root, &path) {
+                    files.push(path);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => errors.push((path, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn walks_nested_directories_in_order() {
+        let root = env::temp_dir().join("minigrep_walks_nested_directories_in_order");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("b/c")).unwrap();
+        fs::write(root.join("z.txt"), "").unwrap();
+        fs::write(root.join("b/a.txt"), "").unwrap();
+        fs::write(root.join("b/c/d.txt"), "").unwrap();
+
+        let mut files = Vec::new();
+        let mut errors = Vec::new();
+        walk(&root, &WalkOptions::default(), &mut files, &mut errors);
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            vec![
+                root.join("b/a.txt"),
+                root.join("b/c/d.txt"),
+                root.join("z.txt")
+            ],
+            files
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops_terminate() {
+        let root = env::temp_dir().join("minigrep_symlink_loops_terminate");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("inner")).unwrap();
+        fs::write(root.join("inner/file.txt"), "").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("inner/loop")).unwrap();
+
+        let options = WalkOptions {
+            follow: true,
+            ..WalkOptions::default()
+        };
+        let mut files = Vec::new();
+        let mut errors = Vec::new();
+        walk(&root, &options, &mut files, &mut errors);
+
+        assert_eq!(vec![root.join("inner/file.txt")], files);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_directories_are_only_followed_when_asked() {
+        let base =
+            env::temp_dir().join("minigrep_symlinked_directories_are_only_followed_when_asked");
+        let _ = fs::remove_dir_all(&base);
+        let (root, elsewhere) = (base.join("root"), base.join("elsewhere"));
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&elsewhere).unwrap();
+        fs::write(root.join("real.txt"), "").unwrap();
+        fs::write(elsewhere.join("linked.txt"), "").unwrap();
+        std::os::unix::fs::symlink(&elsewhere, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(elsewhere.join("linked.txt"), root.join("file-link.txt"))
+            .unwrap();
+        let walked = |follow| {
+            let options = WalkOptions {
+                follow,
+                ..WalkOptions::default()
+            };
+            let mut files = Vec::new();
+            let mut errors = Vec::new();
+            walk(&root, &options, &mut files, &mut errors);
+            assert!(errors.is_empty());
+            files
+        };
+
+        assert_eq!(
+            vec![root.join("file-link.txt"), root.join("real.txt")],
+            walked(false)
+        );
+        assert_eq!(
+            vec![
+                root.join("file-link.txt"),
+                root.join("link/linked.txt"),
+                root.join("real.txt"),
+            ],
+            walked(true)
+        );
+    }
+
+    #[test]
+    fn max_depth_limits_the_walk() {
+        let root = env::temp_dir().join("minigrep_max_depth_limits_the_walk");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("top.txt"), "").unwrap();
+        fs::write(root.join("a/one.txt"), "").unwrap();
+        fs::write(root.join("a/b/two.txt"), "").unwrap();
+        let walked = |max_depth| {
+            let mut files = Vec::new();
+            let options = WalkOptions {
+                max_depth: Some(max_depth),
+                ..WalkOptions::default()
+            };
+            walk(&root, &options, &mut files, &mut Vec::new());
+            files
+        };
+
+        assert_eq!(vec![root.join("top.txt")], walked(0));
+        assert_eq!(
+            vec![root.join("a/one.txt"), root.join("top.txt")],
+            walked(1)
+        );
+        assert_eq!(3, walked(2).len());
+    }
+
+    #[test]
+    fn globs_filter_the_walk() {
+        let root = env::temp_dir().join("minigrep_globs_filter_the_walk");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src/bin")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        for file in [
+            "README.md",
+            "src/lib.rs",
+            "src/bin/main.rs",
+            "target/out.rs",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        let walked = |include: &[&str], exclude: &[&str]| {
+            let options = WalkOptions {
+                include: include
+                    .iter()
+                    .map(|glob| Pattern::new(glob).unwrap())
+                    .collect(),
+                exclude: exclude
+                    .iter()
+                    .map(|glob| Pattern::new(glob).unwrap())
+                    .collect(),
+                ..WalkOptions::default()
+            };
+            let mut files = Vec::new();
+            walk(&root, &options, &mut files, &mut Vec::new());
+            files
+        };
+
+        assert_eq!(4, walked(&[], &[]).len());
+        assert_eq!(
+            vec![root.join("src/bin/main.rs"), root.join("src/lib.rs")],
+            walked(&["*.rs"], &["target"])
+        );
+        assert_eq!(vec![root.join("README.md")], walked(&[], &["*.rs"]));
+        assert_eq!(
+            vec![root.join("src/lib.rs"), root.join("target/out.rs")],
+            walked(&["*.rs"], &["src/bin"])
+        );
+    }
+
+    #[test]
+    fn hidden_entries_are_skipped_by_default() {
+        let root = env::temp_dir().join("minigrep_hidden_entries_are_skipped_by_default");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".git/config"), "").unwrap();
+        fs::write(root.join(".hidden"), "").unwrap();
+        fs::write(root.join("shown.txt"), "").unwrap();
+
+        let mut files = Vec::new();
+        walk(&root, &WalkOptions::default(), &mut files, &mut Vec::new());
+        assert_eq!(vec![root.join("shown.txt")], files);
+
+        let options = WalkOptions {
+            hidden: true,
+            ..WalkOptions::default()
+        };
+        let mut files = Vec::new();
+        walk(
+            &root.join(".git"),
+            &WalkOptions::default(),
+            &mut files,
+            &mut Vec::new(),
+        );
+        assert_eq!(vec![root.join(".git/config")], files);
+        files.clear();
+        walk(&root, &options, &mut files, &mut Vec::new());
+        assert_eq!(
+            vec![
+                root.join(".git/config"),
+                root.join(".hidden"),
+                root.join("shown.txt")
+            ],
+            files
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn fixture(name: &str, contents: &str) -> PathBuf {
+    let dir = env::temp_dir().join("minigrep_exit_codes");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn minigrep(args: &[&str]) -> Option<i32> {
+    Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(args)
+        .env_remove("CASE_INSENSITIVE")
+        .output()
+        .unwrap()
+        .status
+        .code()
+}
+
+#[test]
+fn match_found_exits_zero() {
+    let poem = fixture("match.txt", "Rust:\nTrust me.\n");
+
+    assert_eq!(Some(0), minigrep(&["rust", poem.to_str().unwrap()]));
+    assert_eq!(Some(0), minigrep(&["-q", "rust", poem.to_str().unwrap()]));
+}
+
+#[test]
+fn no_match_exits_one() {
+    let poem = fixture("no_match.txt", "Rust:\nTrust me.\n");
+
+    assert_eq!(Some(1), minigrep(&["duct", poem.to_str().unwrap()]));
+    assert_eq!(Some(1), minigrep(&["-q", "duct", poem.to_str().unwrap()]));
+}
+
+#[test]
+fn errors_exit_two() {
+    assert_eq!(
+        Some(2),
+        minigrep(&["rust", "file_that_does_not_exists.txt"])
+    );
+    assert_eq!(Some(2), minigrep(&["-e", "(unclosed", "Cargo.toml"]));
+    assert_eq!(
+        Some(2),
+        minigrep(&["--color", "sometimes", "rust", "Cargo.toml"])
+    );
+}
+
+#[test]
+fn case_flag_overrides_env_var() {
+    let poem = fixture("case.txt", "Rust:\nTrust me.\n");
+    let status = |flag: &str| {
+        Command::new(env!("CARGO_BIN_EXE_minigrep"))
+            .args([flag, "RUST", poem.to_str().unwrap()])
+            .env("CASE_INSENSITIVE", "1")
+            .output()
+            .unwrap()
+            .status
+            .code()
+    };
+
+    assert_eq!(Some(0), status("-i"));
+    assert_eq!(Some(1), status("-s"));
+}
+
+#[test]
+fn check_only_validates() {
+    let poem = fixture("check.txt", "Rust:\nTrust me.\n");
+
+    assert_eq!(
+        Some(0),
+        minigrep(&["--check", "duct", poem.to_str().unwrap()])
+    );
+    assert_eq!(Some(2), minigrep(&["--check", "rust", "missing.txt"]));
+}
+
+#[test]
+fn verbose_reports_lines_scanned() {
+    let first = fixture("verbose_first.txt", "Rust:\nTrust me.\n");
+    let second = fixture("verbose_second.txt", "safe\nfast\nrust\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(["--verbose", "-s", "rust"])
+        .args([&first, &second])
+        .env_remove("CASE_INSENSITIVE")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(
+        stderr.starts_with("scanned 5 lines, 2 matched in "),
+        "{}",
+        stderr
+    );
+    assert!(!String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("scanned"));
+}
+
+#[test]
+fn progress_is_shown_on_stderr_only() {
+    let dir = env::temp_dir().join("minigrep_progress");
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    fs::write(dir.join("first.txt"), "rust\n").unwrap();
+    fs::write(dir.join("nested").join("second.txt"), "safe\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(["--progress", "-r", "-h", "rust"])
+        .arg(&dir)
+        .env_remove("CASE_INSENSITIVE")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(stderr.contains("searched 1 file: "), "{}", stderr);
+    assert!(stderr.ends_with("\r\x1b[K"), "{:?}", stderr);
+    assert_eq!("rust\n", String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn invert_match_ignores_case_from_env_var() {
+    let poem = fixture("invert_case.txt", "Rust:\nTrust me.\nsafe\nno RUSTY here\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(["-v", "rust", poem.to_str().unwrap()])
+        .env("CASE_INSENSITIVE", "1")
+        .output()
+        .unwrap();
+
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("safe\n", String::from_utf8(output.stdout).unwrap());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(["-v", "rust", poem.to_str().unwrap()])
+        .env_remove("CASE_INSENSITIVE")
+        .output()
+        .unwrap();
+
+    assert_eq!(
+        "Rust:\nsafe\nno RUSTY here\n",
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+fn patterns_file_takes_every_argument_as_a_file() {
+    let patterns = fixture("patterns.txt", "frog\nbog\n");
+    let first = fixture("patterns_first.txt", "a frog\na dog\n");
+    let second = fixture("patterns_second.txt", "a bog\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .arg("-f")
+        .args([&patterns, &first, &second])
+        .env_remove("CASE_INSENSITIVE")
+        .output()
+        .unwrap();
+
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        format!("{}:a frog\n{}:a bog\n", first.display(), second.display()),
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+fn inline_patterns_are_searched_with_the_patterns_file() {
+    let patterns = fixture("inline_patterns.txt", "frog\n");
+    let poem = fixture("inline_poem.txt", "a frog\na dog\na bog\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .arg("-f")
+        .arg(&patterns)
+        .args(["-p", "bog"])
+        .arg(&poem)
+        .env_remove("CASE_INSENSITIVE")
+        .output()
+        .unwrap();
+
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("a frog\na bog\n", String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn json_output_is_valid_json() {
+    let first = fixture("json_first.txt", "a\nb\n");
+    let second = fixture("json_second.txt", "b\n");
+    let run = |flag: Option<&str>| {
+        Command::new(env!("CARGO_BIN_EXE_minigrep"))
+            .args(["--format", "json"])
+            .args(flag)
+            .args(["a".as_ref(), first.as_os_str(), second.as_os_str()])
+            .env_remove("CASE_INSENSITIVE")
+            .output()
+            .unwrap()
+    };
+
+    let output = run(None);
+    assert_eq!(Some(0), output.status.code());
+    let parsed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(1, parsed.as_array().unwrap().len());
+
+    // Modes that print something else than matches can't be JSON.
+    for flag in ["-c", "-l", "-L", "--span"] {
+        let output = run(Some(flag));
+        assert_eq!(Some(2), output.status.code());
+        assert!(output.stdout.is_empty());
+    }
+}
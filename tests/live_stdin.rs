@@ -0,0 +1,37 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn stdin_matches_are_printed_as_they_arrive() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .arg("error")
+        .env_remove("CASE_INSENSITIVE")
+        .env_remove("MINIGREP_COLORS")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            sender.send(line.unwrap()).unwrap();
+        }
+    });
+
+    // Stdin stays open, so each match must come out before the input ends.
+    writeln!(stdin, "ok\nfirst error").unwrap();
+    let first = receiver.recv_timeout(Duration::from_secs(10));
+    writeln!(stdin, "fine\nsecond error").unwrap();
+    let second = receiver.recv_timeout(Duration::from_secs(10));
+    drop(stdin);
+
+    assert_eq!(Ok("first error".to_string()), first);
+    assert_eq!(Ok("second error".to_string()), second);
+    assert_eq!(Some(0), child.wait().unwrap().code());
+}
@@ -0,0 +1,68 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process::Command;
+
+const LINES: usize = 200_000;
+
+/// Prints every line of a large file, which is buffered rather than
+/// flushed a line at a time, and checks that none of it is lost.
+#[test]
+fn prints_many_matches() {
+    let dir = env::temp_dir().join("minigrep_many_matches");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("many.txt");
+    let contents: String = (0..LINES).map(|i| format!("match {}\n", i)).collect();
+    fs::write(&path, &contents).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(["match", path.to_str().unwrap()])
+        .env_remove("CASE_INSENSITIVE")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(contents.as_bytes(), &output.stdout[..]);
+}
+
+/// Counts how often it's flushed.
+struct Flushes {
+    written: Vec<u8>,
+    flushes: usize,
+}
+
+impl Write for Flushes {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn many_matches_are_flushed_once() {
+    let dir = env::temp_dir().join("minigrep_many_matches_flushed");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("many.txt");
+    let contents: String = (0..LINES).map(|i| format!("match {}\n", i)).collect();
+    fs::write(&path, &contents).unwrap();
+
+    let config = minigrep::ConfigBuilder::new()
+        .query("match")
+        .filename(path.to_str().unwrap())
+        .case_sensitive(true)
+        .build()
+        .unwrap();
+    let mut writer = Flushes {
+        written: Vec::new(),
+        flushes: 0,
+    };
+
+    assert!(minigrep::run_to_writer(&config, &mut writer).unwrap());
+    assert_eq!(1, writer.flushes);
+    assert_eq!(contents.as_bytes(), &writer.written[..]);
+}
@@ -0,0 +1,100 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Searches a large file with and without `--mmap`, which have to find
+/// the same lines.
+#[test]
+fn mmap_and_read_agree_on_large_file() {
+    let dir = env::temp_dir().join("minigrep_mmap");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("large.txt");
+    let contents: String = (0..500_000)
+        .map(|i| {
+            format!(
+                "line {} of filler text{}\n",
+                i,
+                if i % 1000 == 0 { " needle" } else { "" }
+            )
+        })
+        .collect();
+    fs::write(&path, &contents).unwrap();
+
+    let search = |extra: &[&str]| -> Vec<u8> {
+        let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+            .args(extra)
+            .args(["needle", path.to_str().unwrap()])
+            .env_remove("CASE_INSENSITIVE")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        output.stdout
+    };
+
+    let read = search(&[]);
+    let mapped = search(&["--mmap"]);
+
+    assert_eq!(read, mapped);
+    assert_eq!(
+        500,
+        read.split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .count()
+    );
+}
+
+/// Compares how long searching a large file takes when it's mapped into
+/// memory and when it's read into a string. It only measures, so it's
+/// left out of the normal test run:
+///
+/// ```text
+/// cargo test --release --test mmap -- --ignored --nocapture
+/// ```
+#[test]
+#[ignore]
+fn benchmark_mmap_against_read_to_string() {
+    let dir = env::temp_dir().join("minigrep_mmap_benchmark");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("large.txt");
+    let contents: String = (0..2_000_000)
+        .map(|i| {
+            format!(
+                "line {} of filler text{}\n",
+                i,
+                if i % 1000 == 0 { " needle" } else { "" }
+            )
+        })
+        .collect();
+    fs::write(&path, &contents).unwrap();
+
+    // The best of several runs, so the page cache is warm for both.
+    let best = |mmap: bool| -> Duration {
+        let config = minigrep::ConfigBuilder::new()
+            .query("needle")
+            .filename(path.to_str().unwrap())
+            .case_sensitive(true)
+            .color(minigrep::ColorChoice::Never)
+            .mmap(mmap)
+            .build()
+            .unwrap();
+        (0..5)
+            .map(|_| {
+                let started = Instant::now();
+                assert!(minigrep::run_to_writer(&config, &mut io::sink()).unwrap());
+                started.elapsed()
+            })
+            .min()
+            .unwrap()
+    };
+
+    let read = best(false);
+    let mapped = best(true);
+    println!(
+        "{} MB: read_to_string {:?}, mmap {:?}",
+        contents.len() / 1_000_000,
+        read,
+        mapped
+    );
+}
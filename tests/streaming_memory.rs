@@ -0,0 +1,79 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::env;
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts the bytes allocated at once, and the most there have been.
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(current, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// The most bytes allocated at once while searching the file.
+fn peak_while_searching(path: &str, stream: bool) -> usize {
+    let config = minigrep::ConfigBuilder::new()
+        .query("ERROR")
+        .filename(path)
+        .case_sensitive(true)
+        .color(minigrep::ColorChoice::Never)
+        .threads(1)
+        .stream(stream)
+        .build()
+        .unwrap();
+
+    PEAK.store(CURRENT.load(Ordering::SeqCst), Ordering::SeqCst);
+    let before = PEAK.load(Ordering::SeqCst);
+    assert!(minigrep::run_to_writer(&config, &mut io::sink()).unwrap());
+    PEAK.load(Ordering::SeqCst) - before
+}
+
+/// Streaming a file keeps only a line at a time, however large the file
+/// is, where reading it whole needs at least its size.
+#[test]
+fn streaming_uses_a_fraction_of_the_file_size() {
+    let dir = env::temp_dir().join("minigrep_streaming_memory");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("large.log");
+    let contents: String = (0..400_000)
+        .map(|i| {
+            if i % 1000 == 0 {
+                format!("{} ERROR something failed\n", i)
+            } else {
+                format!("{} INFO all good\n", i)
+            }
+        })
+        .collect();
+    fs::write(&path, &contents).unwrap();
+    let path = path.to_str().unwrap();
+
+    let read = peak_while_searching(path, false);
+    let streamed = peak_while_searching(path, true);
+
+    assert!(read >= contents.len(), "read whole: {} bytes", read);
+    assert!(
+        streamed < contents.len() / 10,
+        "streamed: {} bytes of {}",
+        streamed,
+        contents.len()
+    );
+}